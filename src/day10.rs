@@ -1,116 +1,243 @@
 use std::collections::HashSet;
 
-fn char_to_int(c: char) -> i32 {
-    c as i32 - 0x30
-}
-
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct Position {
     row: usize,
     col: usize,
 }
 
-fn get_trail_heads(map: &Vec<Vec<i32>>) -> Vec<Position> {
-    let mut trail_heads: Vec<Position> = Vec::new();
+enum StepRule {
+    Strict,
+    UpTo(i32),
+    Descending
+}
+
+struct TerrainRules {
+    step: StepRule,
+    is_trail_head: Box<dyn Fn(i32) -> bool>,
+    is_summit: Box<dyn Fn(i32) -> bool>,
+}
 
-    for row in 0..map.len() {
-        for col in 0..map.len() {
-            if map[row][col] == 0 {
-                trail_heads.push(Position { row, col });
-            }
+impl TerrainRules {
+    fn standard() -> TerrainRules {
+        TerrainRules { step: StepRule::Strict, is_trail_head: Box::new(|height| height == 0), is_summit: Box::new(|height| height == 9) }
+    }
+
+    fn can_step(&self, from: i32, to: i32) -> bool {
+        match self.step {
+            StepRule::Strict => to == from + 1,
+            StepRule::UpTo(max_climb) => to > from && to <= from + max_climb,
+            StepRule::Descending => to == from - 1,
         }
     }
+}
 
-    trail_heads
+struct Topography {
+    cells: Vec<Vec<Option<i32>>>
 }
 
-fn get_destinations(map: &Vec<Vec<i32>>, search_map: &mut Vec<Vec<Option<HashSet<i32>>>>, row: usize, col: usize, height: i32) -> HashSet<i32> {
-    if height == 9 {
-        return vec![(row * 100 + col) as i32].into_iter().collect()
+impl Topography {
+    // Maps are either one character per cell (0-9 then a-z for heights up to 35) or comma separated heights.
+    // Anything that isn't a height ('.', '#', an empty field...) is impassable.
+    fn parse(input: &str) -> Topography {
+        let cells = input.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                if line.contains(',') {
+                    line.split(',').map(|field| field.trim().parse::<i32>().ok()).collect()
+                } else {
+                    line.trim().chars().map(|c| c.to_digit(36).map(|height| height as i32)).collect()
+                }
+            })
+            .collect();
+        Topography { cells }
     }
 
-    match &search_map[row][col] {
-        None => {
-            let mut destinations: HashSet<i32> = HashSet::new();
-
-            if row != 0 && map[row - 1][col] == height + 1 {
-                destinations.extend(get_destinations(map, search_map, row - 1, col, height + 1));
+    fn get_trail_heads(&self, rules: &TerrainRules) -> Vec<Position> {
+        let mut trail_heads: Vec<Position> = Vec::new();
+        for row in 0..self.cells.len() {
+            for col in 0..self.cells[row].len() {
+                if self.cells[row][col].is_some_and(|height| (rules.is_trail_head)(height)) {
+                    trail_heads.push(Position { row, col });
+                }
             }
+        }
+        trail_heads
+    }
 
-            if col != 0 && map[row][col - 1] == height + 1 {
-                destinations.extend(get_destinations(map, search_map, row, col - 1, height + 1));
-            }
+    fn get_height(&self, position: &Position) -> Option<i32> {
+        self.cells[position.row][position.col]
+    }
 
-            if row + 1 != map.len() && map[row + 1][col] == height + 1 {
-                destinations.extend(get_destinations(map, search_map, row + 1, col, height + 1));
+    // Passable neighbours up, left, down then right.
+    fn get_neighbours(&self, position: &Position) -> Vec<Position> {
+        let mut neighbours: Vec<Position> = Vec::new();
+        for (row_delta, col_delta) in [(-1, 0), (0, -1), (1, 0), (0, 1)] {
+            let row = position.row as i32 + row_delta;
+            let col = position.col as i32 + col_delta;
+            if row < 0 || col < 0 || row as usize >= self.cells.len() || col as usize >= self.cells[row as usize].len() {
+                continue;
             }
-
-            if col + 1 != map.len() && map[row][col + 1] == height + 1 {
-                destinations.extend(get_destinations(map, search_map, row, col + 1, height + 1));
+            let neighbour = Position { row: row as usize, col: col as usize };
+            if self.get_height(&neighbour).is_some() {
+                neighbours.push(neighbour);
             }
+        }
+        neighbours
+    }
 
-            search_map[row][col] = Some(destinations.clone());
-            destinations
-        },
-        Some(destinations) => destinations.clone()
+    fn get_next_steps(&self, rules: &TerrainRules, position: &Position) -> Vec<Position> {
+        let height = self.get_height(position).unwrap();
+        self.get_neighbours(position).into_iter()
+            .filter(|neighbour| rules.can_step(height, self.get_height(neighbour).unwrap()))
+            .collect()
     }
-}
 
-fn part1(input: &String) -> String {
-    let size = input.lines().count();
-    let mut total_score = 0;
-    let map: Vec<Vec<i32>> = input.lines().map(|line| line.chars().map(char_to_int).collect()).collect();
-    let mut search_map: Vec<Vec<Option<HashSet<i32>>>> = vec![vec![None; size]; size];
+    fn get_summits(&self, rules: &TerrainRules, search_map: &mut Vec<Vec<Option<HashSet<Position>>>>, position: Position) -> HashSet<Position> {
+        if (rules.is_summit)(self.get_height(&position).unwrap()) {
+            return vec![position].into_iter().collect()
+        }
 
-    for trail_head in get_trail_heads(&map) {
-        total_score += get_destinations(&map, &mut search_map, trail_head.row, trail_head.col, 0).len();
+        match &search_map[position.row][position.col] {
+            None => {
+                let mut summits: HashSet<Position> = HashSet::new();
+                for next_step in self.get_next_steps(rules, &position) {
+                    summits.extend(self.get_summits(rules, search_map, next_step));
+                }
+                search_map[position.row][position.col] = Some(summits.clone());
+                summits
+            },
+            Some(summits) => summits.clone()
+        }
     }
 
-    format!("{}", total_score)
-}
+    fn get_trail_count(&self, rules: &TerrainRules, search_map: &mut Vec<Vec<Option<u64>>>, position: Position) -> u64 {
+        if (rules.is_summit)(self.get_height(&position).unwrap()) {
+            return 1
+        }
 
-fn get_trails(map: &Vec<Vec<i32>>, search_map: &mut Vec<Vec<i32>>, row: usize, col: usize, height: i32) -> i32 {
-    if height == 9 {
-        return 1
+        match search_map[position.row][position.col] {
+            None => {
+                let mut trail_count = 0;
+                for next_step in self.get_next_steps(rules, &position) {
+                    trail_count += self.get_trail_count(rules, search_map, next_step);
+                }
+                search_map[position.row][position.col] = Some(trail_count);
+                trail_count
+            },
+            Some(trail_count) => trail_count
+        }
     }
 
-    match search_map[row][col] {
-        -1 => {
-            let mut trail_count: i32 = 0;
+    // Ways to climb from any trail head to this cell. Trails end at their first summit, so none carry on past one.
+    fn get_ascent_count(&self, rules: &TerrainRules, search_map: &mut Vec<Vec<Option<u64>>>, position: Position) -> u64 {
+        if let Some(ascent_count) = search_map[position.row][position.col] {
+            return ascent_count
+        }
 
-            if row != 0 && map[row - 1][col] == height + 1 {
-                trail_count += get_trails(map, search_map, row - 1, col, height + 1);
+        let height = self.get_height(&position).unwrap();
+        let mut ascent_count = if (rules.is_trail_head)(height) { 1 } else { 0 };
+        for neighbour in self.get_neighbours(&position) {
+            let neighbour_height = self.get_height(&neighbour).unwrap();
+            if rules.can_step(neighbour_height, height) && !(rules.is_summit)(neighbour_height) {
+                ascent_count += self.get_ascent_count(rules, search_map, neighbour);
             }
+        }
+        search_map[position.row][position.col] = Some(ascent_count);
+        ascent_count
+    }
 
-            if col != 0 && map[row][col - 1] == height + 1 {
-                trail_count += get_trails(map, search_map, row, col - 1, height + 1);
-            }
+    fn score(&self, rules: &TerrainRules) -> usize {
+        let mut search_map = self.cells.iter().map(|line| vec![None; line.len()]).collect();
+        self.get_trail_heads(rules).into_iter().map(|trail_head| self.get_summits(rules, &mut search_map, trail_head).len()).sum()
+    }
 
-            if row + 1 != map.len() && map[row + 1][col] == height + 1 {
-                trail_count += get_trails(map, search_map, row + 1, col, height + 1);
-            }
+    fn rating(&self, rules: &TerrainRules) -> u64 {
+        let mut search_map = self.cells.iter().map(|line| vec![None; line.len()]).collect();
+        self.get_trail_heads(rules).into_iter().map(|trail_head| self.get_trail_count(rules, &mut search_map, trail_head)).sum()
+    }
+}
 
-            if col + 1 != map.len() && map[row][col + 1] == height + 1 {
-                trail_count += get_trails(map, search_map, row, col + 1, height + 1);
-            }
+fn part1(input: &String) -> String {
+    format!("{}", Topography::parse(input).score(&TerrainRules::standard()))
+}
 
-            search_map[row][col] = trail_count;
-            trail_count
-        },
-        trail_count => trail_count
+fn part2(input: &String) -> String {
+    format!("{}", Topography::parse(input).rating(&TerrainRules::standard()))
+}
+
+struct Trails<'a> {
+    topography: &'a Topography,
+    rules: &'a TerrainRules,
+    trail_heads: Vec<Position>,
+    path: Vec<Position>,
+    next_steps: Vec<Vec<Position>>,
+}
+
+impl Trails<'_> {
+    fn new<'a>(topography: &'a Topography, rules: &'a TerrainRules) -> Trails<'a> {
+        let mut trail_heads = topography.get_trail_heads(rules);
+        trail_heads.reverse();
+        Trails { topography, rules, trail_heads, path: Vec::new(), next_steps: Vec::new() }
+    }
+
+    fn push(&mut self, position: Position) {
+        let mut next_steps = self.topography.get_next_steps(self.rules, &position);
+        next_steps.reverse();
+        self.path.push(position);
+        self.next_steps.push(next_steps);
     }
 }
 
-fn part2(input: &String) -> String {
-    let size = input.lines().count();
-    let mut total_score = 0;
-    let map: Vec<Vec<i32>> = input.lines().map(|line| line.chars().map(char_to_int).collect()).collect();
-    let mut search_map: Vec<Vec<i32>> = vec![vec![-1; size]; size];
+impl Iterator for Trails<'_> {
+    type Item = Vec<Position>;
 
-    for trail_head in get_trail_heads(&map) {
-        total_score += get_trails(&map, &mut search_map, trail_head.row, trail_head.col, 0);
+    fn next(&mut self) -> Option<Vec<Position>> {
+        loop {
+            if self.path.is_empty() {
+                let trail_head = self.trail_heads.pop()?;
+                self.push(trail_head);
+            }
+
+            let position = *self.path.last().unwrap();
+            let at_summit = (self.rules.is_summit)(self.topography.get_height(&position).unwrap());
+            match self.next_steps.last_mut().unwrap().pop() {
+                Some(next_step) if !at_summit => self.push(next_step),
+                _ => {
+                    let trail = if at_summit { Some(self.path.clone()) } else { None };
+                    self.path.pop();
+                    self.next_steps.pop();
+                    if trail.is_some() {
+                        return trail
+                    }
+                },
+            }
+        }
     }
+}
+
+// The number of trails through a cell is the number of ways to climb to it from a trail head multiplied by the
+// number of ways to carry on to a summit.
+fn get_heatmap(topography: &Topography, rules: &TerrainRules) -> Vec<Vec<u64>> {
+    let mut ascents = topography.cells.iter().map(|line| vec![None; line.len()]).collect();
+    let mut descents = topography.cells.iter().map(|line| vec![None; line.len()]).collect();
+
+    topography.cells.iter().enumerate().map(|(row, line)| {
+        (0..line.len()).map(|col| match line[col] {
+            None => 0,
+            Some(_) => {
+                let position = Position { row, col };
+                topography.get_ascent_count(rules, &mut ascents, position) * topography.get_trail_count(rules, &mut descents, position)
+            },
+        }).collect()
+    }).collect()
+}
 
-    format!("{}", total_score)
+fn heatmap_to_csv(heatmap: &[Vec<u64>]) -> String {
+    heatmap.iter()
+        .map(|line| line.iter().map(ToString::to_string).collect::<Vec<String>>().join(","))
+        .collect::<Vec<String>>()
+        .join("\n")
 }
 
 fn main() {
@@ -119,13 +246,30 @@ fn main() {
     println!("Part1: {}", part1(input1));
 
     println!("Part2: {}", part2(input1));
+
+    let topography = Topography::parse(input1);
+    let climbing = TerrainRules { step: StepRule::UpTo(2), ..TerrainRules::standard() };
+    println!("Climbing up to 2 at a time: {} summits, {} trails", topography.score(&climbing), topography.rating(&climbing));
+    let descending = TerrainRules { step: StepRule::Descending, is_trail_head: Box::new(|height| height == 9), is_summit: Box::new(|height| height == 0) };
+    println!("Descending: {} summits, {} trails", topography.score(&descending), topography.rating(&descending));
+
+    let rules = TerrainRules::standard();
+    if std::env::args().any(|arg| arg == "--trails") {
+        if let Some(trail) = Trails::new(&topography, &rules).next() {
+            println!("First trail: {:?}", trail.iter().map(|position| (position.row, position.col)).collect::<Vec<(usize, usize)>>());
+        }
+        println!("Trails: {}", Trails::new(&topography, &rules).count());
+    }
+    if std::env::args().any(|arg| arg == "--heatmap") {
+        let heatmap = get_heatmap(&topography, &rules);
+        println!("Heatmap:\n{}", heatmap_to_csv(&heatmap));
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::part1;
-    use crate::part2;
+    use crate::{get_heatmap, heatmap_to_csv, part1, part2, Position, StepRule, TerrainRules, Topography, Trails};
 
     #[test]
     fn test_part1() {
@@ -156,4 +300,125 @@ mod tests {
 
         assert_eq!(part2(&example1.to_string()), expected1);
     }
+
+    #[test]
+    fn test_trails() {
+        let example1 = r#"0123
+1234
+8765
+9876"#;
+        let topography = Topography::parse(example1);
+
+        let trails: Vec<Vec<Position>> = Trails::new(&topography, &TerrainRules::standard()).collect();
+        assert_eq!(trails.len(), 16);
+        assert!(trails.iter().all(|trail| trail.len() == 10));
+        assert_eq!(trails[0][..3], [Position { row: 0, col: 0 }, Position { row: 1, col: 0 }, Position { row: 1, col: 1 }]);
+        assert_eq!(trails[0][9], Position { row: 3, col: 0 });
+    }
+
+    #[test]
+    fn test_trails_match_part2() {
+        let example1 = r#"89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732"#;
+        let topography = Topography::parse(example1);
+        let rules = TerrainRules::standard();
+
+        assert_eq!(format!("{}", Trails::new(&topography, &rules).count()), part2(&example1.to_string()));
+
+        let heatmap = get_heatmap(&topography, &rules);
+        let heads: u64 = topography.get_trail_heads(&rules).iter().map(|head| heatmap[head.row][head.col]).sum();
+        assert_eq!(heads, 81);
+        for trail in Trails::new(&topography, &rules) {
+            for position in trail {
+                assert!(heatmap[position.row][position.col] > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let example1 = r#"0123
+1234
+8765
+9876"#;
+        let topography = Topography::parse(example1);
+
+        assert_eq!(heatmap_to_csv(&get_heatmap(&topography, &TerrainRules::standard())), "16,12,8,4\n4,8,12,16\n4,8,12,16\n16,12,8,4");
+    }
+
+    #[test]
+    fn test_part1_impassable() {
+        let example1 = r#"...0...
+...1...
+...2...
+6543456
+7.....7
+8.....8
+9.....9"#;
+        let expected1 = "2".to_string();
+
+        assert_eq!(part1(&example1.to_string()), expected1);
+    }
+
+    #[test]
+    fn test_part2_impassable() {
+        let example1 = r#".....0.
+..4321.
+..5..2.
+..6543.
+..7..4.
+..8765.
+..9...."#;
+        let expected1 = "3".to_string();
+
+        assert_eq!(part2(&example1.to_string()), expected1);
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let example1 = r#"0,2,4
+10,#,6
+12,10,8"#;
+        let topography = Topography::parse(example1);
+        let rules = TerrainRules { step: StepRule::UpTo(2), is_trail_head: Box::new(|height| height == 0), is_summit: Box::new(|height| height >= 12) };
+        assert_eq!(topography.score(&rules), 1);
+        assert_eq!(topography.rating(&rules), 1);
+        assert_eq!(topography.rating(&TerrainRules::standard()), 0);
+
+        let example2 = r#"0123
+bc.4
+a..5
+9876"#;
+        let topography = Topography::parse(example2);
+        let rules = TerrainRules { step: StepRule::Descending, is_trail_head: Box::new(|height| height == 12), is_summit: Box::new(|height| height == 0) };
+        assert_eq!(topography.score(&rules), 1);
+        assert_eq!(topography.rating(&rules), 1);
+        let rules = TerrainRules { step: StepRule::UpTo(3), is_trail_head: Box::new(|height| height == 0), is_summit: Box::new(|height| height == 12) };
+        assert_eq!(topography.score(&rules), 1);
+
+        // Impassable cells and custom rules carry through to trail listings and heatmaps
+        let rules = TerrainRules { step: StepRule::Descending, is_trail_head: Box::new(|height| height == 12), is_summit: Box::new(|height| height == 0) };
+        let trails: Vec<Vec<Position>> = Trails::new(&topography, &rules).collect();
+        assert_eq!(trails.len(), 1);
+        assert_eq!(trails[0].len(), 13);
+        assert_eq!(trails[0][0], Position { row: 1, col: 1 });
+        assert_eq!(heatmap_to_csv(&get_heatmap(&topography, &rules)), "1,1,1,1\n1,1,0,1\n1,0,0,1\n1,1,1,1");
+    }
+
+    #[test]
+    fn test_wide_map() {
+        // The summits at (0, 1000) and (1, 0) are both reached from the single trail head
+        let top: Vec<String> = (0..1001).map(|col| match col { 999 => "501".to_string(), 1000 => "502".to_string(), _ => "#".to_string() }).collect();
+        let bottom: Vec<String> = (0..1001_i32).map(|col| if col == 1000 { "#".to_string() } else { (col - 500).abs().to_string() }).collect();
+        let topography = Topography::parse(&format!("{}\n{}", top.join(","), bottom.join(",")));
+        let rules = TerrainRules { step: StepRule::UpTo(2), is_trail_head: Box::new(|height| height == 0), is_summit: Box::new(|height| height >= 500 && height % 2 == 0) };
+        assert_eq!(topography.score(&rules), 2);
+        assert_eq!(Trails::new(&topography, &rules).count(), 2);
+    }
 }
@@ -1,61 +1,411 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
 
-fn add_stone(stone_counts: &mut HashMap<String, i64>, stone: String, count: i64) {
-    *stone_counts.entry(stone).or_insert(0) += count;
+enum DigitCount {
+    MultipleOfParts,
+    Odd,
+    Even
 }
 
-fn drop_leading_zeros(number: &str) -> &str {
-    let mut start_index = 0;
-    for digit in number.chars() {
-        if digit != '0' {
-            return &number[start_index..]
+enum Rule {
+    Replace(u64, u64),
+    Split { parts: u32, digit_count: DigitCount },
+    Multiply(u64)
+}
+
+struct RuleSet {
+    base: u64,
+    rules: Vec<Rule>
+}
+
+impl FromStr for RuleSet {
+    type Err = String;
+
+    // One rule per line or per ';', tried in order until one applies:
+    //   base <b>            digits are counted and split in base b (10 by default)
+    //   <a> -> <b>          a stone engraved with a becomes b
+    //   split <n> [odd|even] a stone splits into n parts when its digit count is a multiple of n (or odd, or even)
+    //   * <m>               the stone is multiplied by m
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut rule_set = RuleSet { base: 10, rules: Vec::new() };
+        for line in input.split([';', '\n']).map(str::trim).filter(|line| !line.is_empty()) {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let number = |word: &str| word.parse::<u64>().map_err(|_| format!("Invalid number '{}' in rule '{}'", word, line));
+            let part_count = |word: &str| match u32::try_from(number(word)?) {
+                Ok(parts) if parts >= 2 => Ok(parts),
+                _ => Err(format!("A stone must split into at least 2 parts in rule '{}'", line)),
+            };
+            match words.as_slice() {
+                ["base", base] => rule_set.base = number(base)?,
+                [from, "->", to] => rule_set.rules.push(Rule::Replace(number(from)?, number(to)?)),
+                ["split", parts] => rule_set.rules.push(Rule::Split { parts: part_count(parts)?, digit_count: DigitCount::MultipleOfParts }),
+                ["split", parts, "odd"] => rule_set.rules.push(Rule::Split { parts: part_count(parts)?, digit_count: DigitCount::Odd }),
+                ["split", parts, "even"] => rule_set.rules.push(Rule::Split { parts: part_count(parts)?, digit_count: DigitCount::Even }),
+                ["*", multiplier] => rule_set.rules.push(Rule::Multiply(number(multiplier)?)),
+                _ => return Err(format!("Unknown rule '{}'", line)),
+            }
+        }
+        if rule_set.base < 2 {
+            return Err(format!("Invalid base {}", rule_set.base))
         }
-        start_index += 1
+        Ok(rule_set)
     }
-    "0"
 }
 
-fn blink(stone_counts: &HashMap<String, i64>) -> HashMap<String, i64> {
-    let mut updated_stone_counts: HashMap<String, i64> = HashMap::new();
-    for (stone, &count) in stone_counts.iter() {
-        if stone == "0" {
-            add_stone(&mut updated_stone_counts, "1".to_string(), count);
-        } else if stone.len() % 2 == 0 {
-            add_stone(&mut updated_stone_counts, stone[..stone.len() / 2].to_string(), count);
-            add_stone(&mut updated_stone_counts, drop_leading_zeros(&stone[stone.len() / 2..]).to_string(), count);
-        } else {
-            add_stone(&mut updated_stone_counts, format!("{}", stone.parse::<i64>().unwrap() * 2024), count);
+impl RuleSet {
+    fn standard() -> RuleSet {
+        "0 -> 1; split 2; * 2024".parse().unwrap()
+    }
+
+    fn count_digits(&self, mut stone: u64) -> u32 {
+        let mut digits = 1;
+        while stone >= self.base {
+            stone /= self.base;
+            digits += 1;
         }
+        digits
+    }
+
+    // The first parts take the extra digit when the digit count doesn't divide evenly, so 12345 split in two
+    // becomes 123 and 45.
+    fn split(&self, stone: u64, digits: u32, parts: u32) -> Vec<u64> {
+        let mut part_digits: Vec<u32> = (0..parts).map(|part| digits / parts + if part < digits % parts { 1 } else { 0 }).collect();
+        part_digits.reverse();
+
+        let mut remaining = stone;
+        let mut split_stones: Vec<u64> = Vec::new();
+        for part in part_digits {
+            let divisor = self.base.pow(part);
+            split_stones.push(remaining % divisor);
+            remaining /= divisor;
+        }
+        split_stones.reverse();
+        split_stones
+    }
+
+    fn next_stones(&self, stone: u64) -> Result<Vec<u64>, String> {
+        let digits = self.count_digits(stone);
+        for rule in &self.rules {
+            match rule {
+                Rule::Replace(from, to) => {
+                    if stone == *from {
+                        return Ok(vec![*to])
+                    }
+                },
+                Rule::Split { parts, digit_count } => {
+                    let applies = match digit_count {
+                        DigitCount::MultipleOfParts => digits.is_multiple_of(*parts),
+                        DigitCount::Odd => !digits.is_multiple_of(2),
+                        DigitCount::Even => digits.is_multiple_of(2),
+                    };
+                    if applies && digits >= *parts {
+                        return Ok(self.split(stone, digits, *parts))
+                    }
+                },
+                Rule::Multiply(multiplier) => {
+                    return stone.checked_mul(*multiplier)
+                        .map(|next| vec![next])
+                        .ok_or(format!("Stone {} times {} doesn't fit in 64 bits", stone, multiplier))
+                }
+            }
+        }
+        Ok(vec![stone])
     }
-    updated_stone_counts
 }
 
-fn part1(input: &String) -> String {
-    let mut stone_counts: HashMap<String, i64> = HashMap::new();
+struct Counting {
+    modulus: Option<u64>
+}
+
+impl Counting {
+    fn add(&self, a: u128, b: u128) -> Result<u128, String> {
+        match self.modulus {
+            None => a.checked_add(b).ok_or("Stone count overflowed u128, count with a modulus instead".to_string()),
+            Some(modulus) => Ok((a + b) % modulus as u128),
+        }
+    }
+}
+
+const MAX_STONE_VALUES: usize = 1_000_000;
+
+struct StoneGraph {
+    index: HashMap<u64, usize>,
+    children: Vec<Vec<usize>>,
+}
+
+impl StoneGraph {
+    fn build(stones: &[u64], rules: &RuleSet) -> Result<StoneGraph, String> {
+        let mut graph = StoneGraph { index: HashMap::new(), children: Vec::new() };
+        let mut queue: VecDeque<u64> = VecDeque::new();
+        for &stone in stones {
+            graph.get_or_add(stone, &mut queue);
+        }
+
+        while let Some(stone) = queue.pop_front() {
+            if graph.children.len() > MAX_STONE_VALUES {
+                return Err(format!("More than {} distinct stone values, these rules don't settle down", MAX_STONE_VALUES))
+            }
+            let children = rules.next_stones(stone)?.into_iter().map(|child| graph.get_or_add(child, &mut queue)).collect();
+            graph.children[graph.index[&stone]] = children;
+        }
+
+        Ok(graph)
+    }
+
+    fn get_or_add(&mut self, stone: u64, queue: &mut VecDeque<u64>) -> usize {
+        if let Some(&index) = self.index.get(&stone) {
+            return index
+        }
+        let index = self.children.len();
+        self.index.insert(stone, index);
+        self.children.push(Vec::new());
+        queue.push_back(stone);
+        index
+    }
 
-    for stone in input.split_whitespace() {
-        add_stone(&mut stone_counts, stone.to_string(), 1)
+    fn blink(&self, counts: &[u128], counting: &Counting) -> Result<Vec<u128>, String> {
+        let mut updated_counts = vec![0; counts.len()];
+        for (node, &count) in counts.iter().enumerate() {
+            if count != 0 {
+                for &child in &self.children[node] {
+                    updated_counts[child] = counting.add(updated_counts[child], count)?;
+                }
+            }
+        }
+        Ok(updated_counts)
     }
 
-    for _ in 0..25 {
-        stone_counts = blink(&stone_counts);
+    // Stone totals after 0, 1, 2... blinks, one per entry of the result.
+    fn get_totals(&self, stones: &[u64], blinks: u64, counting: &Counting) -> Result<Vec<u128>, String> {
+        let mut counts: Vec<u128> = vec![0; self.children.len()];
+        for stone in stones {
+            let node = self.index[stone];
+            counts[node] = counting.add(counts[node], 1)?;
+        }
+
+        let mut totals: Vec<u128> = Vec::new();
+        for blink in 0..=blinks {
+            totals.push(counts.iter().try_fold(0, |total, &count| counting.add(total, count))?);
+            if blink < blinks {
+                counts = self.blink(&counts, counting)?;
+            }
+        }
+        Ok(totals)
     }
 
-    format!("{}", stone_counts.values().sum::<i64>())
+    // The stone total after t blinks is a linear recurrence of order at most the number of distinct stones. With a
+    // prime modulus that recurrence can be recovered from the first few totals, after which any blink count is a
+    // matter of raising x to the blink count modulo the recurrence's characteristic polynomial. A square-free
+    // modulus is counted modulo each of its primes and put back together with the Chinese remainder theorem.
+    fn count(&self, stones: &[u64], blinks: u64, counting: &Counting) -> Result<u128, String> {
+        let terms = 2 * self.children.len() as u64 + 2;
+        match counting.modulus {
+            Some(0) => Err("The modulus has to be at least 1".to_string()),
+            Some(modulus) if blinks > terms && is_prime(modulus) => {
+                let totals = self.get_totals(stones, terms - 1, counting)?;
+                let recurrence = find_recurrence(&totals, modulus as u128);
+                Ok(get_term(&totals, &recurrence, blinks, modulus as u128))
+            },
+            Some(modulus) if blinks > terms => {
+                let mut total = 0;
+                let mut product = 1;
+                for prime in get_prime_factors(modulus)? {
+                    let remainder = self.count(stones, blinks, &Counting { modulus: Some(prime) })?;
+                    total = combine_remainders(total, product, remainder, prime as u128);
+                    product *= prime as u128;
+                }
+                Ok(total)
+            },
+            _ => Ok(*self.get_totals(stones, blinks, counting)?.last().unwrap()),
+        }
+    }
 }
 
-fn part2(input: &String) -> String {
-    let mut stone_counts: HashMap<String, i64> = HashMap::new();
+fn pow_mod(mut base: u128, mut exponent: u128, modulus: u128) -> u128 {
+    let mut result = 1 % modulus;
+    base %= modulus;
+    while exponent > 0 {
+        if exponent % 2 == 1 {
+            result = result * base % modulus;
+        }
+        base = base * base % modulus;
+        exponent /= 2;
+    }
+    result
+}
+
+fn is_prime(number: u64) -> bool {
+    if number < 2 {
+        return false
+    }
+    let witnesses = [2_u64, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if witnesses.contains(&number) {
+        return true
+    }
+    if witnesses.iter().any(|witness| number.is_multiple_of(*witness)) {
+        return false
+    }
+
+    let mut odd_part = number - 1;
+    let mut twos = 0;
+    while odd_part.is_multiple_of(2) {
+        odd_part /= 2;
+        twos += 1;
+    }
 
-    for stone in input.split_whitespace() {
-        add_stone(&mut stone_counts, stone.to_string(), 1)
+    let number = number as u128;
+    'witness: for witness in witnesses {
+        let mut x = pow_mod(witness as u128, odd_part as u128, number);
+        if x == 1 || x == number - 1 {
+            continue;
+        }
+        for _ in 1..twos {
+            x = x * x % number;
+            if x == number - 1 {
+                continue 'witness;
+            }
+        }
+        return false
     }
+    true
+}
 
-    for _ in 0..75 {
-        stone_counts = blink(&stone_counts);
+// Distinct primes of a square-free modulus. Anything else would need a recurrence over a ring without division.
+fn get_prime_factors(modulus: u64) -> Result<Vec<u64>, String> {
+    let mut primes = Vec::new();
+    let mut remaining = modulus;
+    let mut divisor = 2;
+    while divisor <= 1_000_000 && divisor * divisor <= remaining {
+        if remaining.is_multiple_of(divisor) {
+            remaining /= divisor;
+            if remaining.is_multiple_of(divisor) {
+                return Err(format!("Unable to fast-forward modulo {}, it isn't square-free", modulus))
+            }
+            primes.push(divisor);
+        }
+        divisor += 1;
+    }
+    if remaining > 1 {
+        if !is_prime(remaining) {
+            return Err(format!("Unable to fast-forward modulo {}, {} doesn't factor", modulus, remaining))
+        }
+        primes.push(remaining);
     }
+    Ok(primes)
+}
 
-    format!("{}", stone_counts.values().sum::<i64>())
+// The number that is total modulo product and remainder modulo prime, where the prime doesn't divide the product.
+fn combine_remainders(total: u128, product: u128, remainder: u128, prime: u128) -> u128 {
+    let difference = (remainder + prime - total % prime) % prime;
+    total + product * (difference * pow_mod(product % prime, prime - 2, prime) % prime)
+}
+
+// Berlekamp-Massey: returns c where sequence[k] = c[0] * sequence[k - 1] + c[1] * sequence[k - 2] + ...
+fn find_recurrence(sequence: &[u128], modulus: u128) -> Vec<u128> {
+    let mut current: Vec<u128> = Vec::new();
+    let mut previous: Vec<u128> = Vec::new();
+    let mut previous_index = 0;
+    let mut previous_discrepancy = 0;
+
+    for index in 0..sequence.len() {
+        let mut discrepancy = sequence[index];
+        for (offset, coefficient) in current.iter().enumerate() {
+            discrepancy = (discrepancy + modulus - coefficient * sequence[index - offset - 1] % modulus) % modulus;
+        }
+        if discrepancy == 0 {
+            continue;
+        }
+
+        if previous_discrepancy == 0 {
+            current = vec![0; index + 1];
+            previous_index = index;
+            previous_discrepancy = discrepancy;
+            continue;
+        }
+
+        let scale = discrepancy * pow_mod(previous_discrepancy, modulus - 2, modulus) % modulus;
+        let mut updated = current.clone();
+        let shift = index - previous_index - 1;
+        if updated.len() < shift + 1 + previous.len() {
+            updated.resize(shift + 1 + previous.len(), 0);
+        }
+        updated[shift] = (updated[shift] + scale) % modulus;
+        for (offset, coefficient) in previous.iter().enumerate() {
+            updated[shift + 1 + offset] = (updated[shift + 1 + offset] + modulus - scale * coefficient % modulus) % modulus;
+        }
+
+        if index - previous_index + previous.len() >= current.len() {
+            previous = current;
+            previous_index = index;
+            previous_discrepancy = discrepancy;
+        }
+        current = updated;
+    }
+
+    current
+}
+
+fn multiply_polynomials(first: &[u128], second: &[u128], recurrence: &[u128], modulus: u128) -> Vec<u128> {
+    let order = recurrence.len();
+    let mut product = vec![0; order * 2];
+    for (i, &a) in first.iter().enumerate() {
+        if a != 0 {
+            for (j, &b) in second.iter().enumerate() {
+                product[i + j] = (product[i + j] + a * b) % modulus;
+            }
+        }
+    }
+
+    for power in (order..product.len()).rev() {
+        let coefficient = product[power];
+        if coefficient != 0 {
+            for (offset, &c) in recurrence.iter().enumerate() {
+                product[power - offset - 1] = (product[power - offset - 1] + coefficient * c) % modulus;
+            }
+        }
+    }
+    product.truncate(order);
+    product
+}
+
+fn get_term(sequence: &[u128], recurrence: &[u128], index: u64, modulus: u128) -> u128 {
+    if recurrence.is_empty() {
+        return 0
+    }
+
+    let order = recurrence.len();
+    let mut result = vec![0; order];
+    result[0] = 1;
+    let mut base = vec![0; order];
+    if order == 1 {
+        base[0] = recurrence[0];
+    } else {
+        base[1] = 1;
+    }
+
+    let mut exponent = index;
+    while exponent > 0 {
+        if exponent % 2 == 1 {
+            result = multiply_polynomials(&result, &base, recurrence, modulus);
+        }
+        base = multiply_polynomials(&base, &base, recurrence, modulus);
+        exponent /= 2;
+    }
+
+    result.iter().zip(sequence).fold(0, |total, (a, b)| (total + a * b) % modulus)
+}
+
+fn count_stones(input: &str, rules: &RuleSet, blinks: u64, modulus: Option<u64>) -> Result<u128, String> {
+    let stones: Vec<u64> = input.split_whitespace().map(|stone| stone.parse::<u64>().unwrap()).collect();
+    StoneGraph::build(&stones, rules)?.count(&stones, blinks, &Counting { modulus })
+}
+
+fn part1(input: &String) -> String {
+    format!("{}", count_stones(input, &RuleSet::standard(), 25, None).unwrap())
+}
+
+fn part2(input: &String) -> String {
+    format!("{}", count_stones(input, &RuleSet::standard(), 75, None).unwrap())
 }
 
 fn main() {
@@ -64,12 +414,20 @@ fn main() {
     println!("Part1: {}", part1(input1));
 
     println!("Part2: {}", part2(input1));
+
+    println!("1000000 blinks (mod 1000000007): {}", count_stones(input1, &RuleSet::standard(), 1_000_000, Some(1_000_000_007)).unwrap());
+
+    let variant = "0 -> 1; split 3; split 2 odd; * 2025";
+    match count_stones(input1, &variant.parse().unwrap(), 75, None) {
+        Ok(count) => println!("75 blinks with '{}': {}", variant, count),
+        Err(message) => println!("75 blinks with '{}': {}", variant, message),
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::part1;
+    use crate::{count_stones, get_prime_factors, is_prime, part1, part2, Counting, RuleSet, StoneGraph};
 
     #[test]
     fn test_part1() {
@@ -78,4 +436,98 @@ mod tests {
 
         assert_eq!(part1(&example1.to_string()), expected1);
     }
+
+    #[test]
+    fn test_next_stones() {
+        let rules = RuleSet::standard();
+        assert_eq!(rules.next_stones(0), Ok(vec![1]));
+        assert_eq!(rules.next_stones(1000), Ok(vec![10, 0]));
+        assert_eq!(rules.next_stones(99), Ok(vec![9, 9]));
+        assert_eq!(rules.next_stones(125), Ok(vec![253000]));
+    }
+
+    #[test]
+    fn test_rule_variants() {
+        let rules: RuleSet = "0 -> 1; split 3; split 2 odd; * 3".parse().unwrap();
+        assert_eq!(rules.next_stones(123456), Ok(vec![12, 34, 56]));
+        assert_eq!(rules.next_stones(12345), Ok(vec![123, 45]));
+        assert_eq!(rules.next_stones(7), Ok(vec![21]));
+        assert_eq!(rules.next_stones(1001), Ok(vec![3003]));
+        assert_eq!(rules.next_stones(100200), Ok(vec![10, 2, 0]));
+
+        let rules: RuleSet = "base 2\n0 -> 1\nsplit 2 even\n* 3".parse().unwrap();
+        assert_eq!(rules.next_stones(0b1011), Ok(vec![0b10, 0b11]));
+        assert_eq!(rules.next_stones(0b101), Ok(vec![0b1111]));
+        assert_eq!(count_stones("0", &rules, 4, None), Ok(2));
+
+        // Tripling forever runs out of room, it isn't an answer
+        let rules: RuleSet = "* 3".parse().unwrap();
+        assert_eq!(rules.next_stones(u64::MAX / 2), Err(format!("Stone {} times 3 doesn't fit in 64 bits", u64::MAX / 2)));
+        assert!(count_stones("1", &rules, 50, None).is_err());
+
+        assert!("split two".parse::<RuleSet>().is_err());
+        assert!("+ 1".parse::<RuleSet>().is_err());
+        assert!("base 1".parse::<RuleSet>().is_err());
+        assert!("split 1; * 2".parse::<RuleSet>().is_err());
+        assert!("split 0 odd".parse::<RuleSet>().is_err());
+        assert_eq!("split 4294967298".parse::<RuleSet>().err(), Some("A stone must split into at least 2 parts in rule 'split 4294967298'".to_string()));
+    }
+
+    #[test]
+    fn test_count_stones() {
+        let example1 = r#"125 17"#;
+
+        assert_eq!(count_stones(example1, &RuleSet::standard(), 6, None), Ok(22));
+        assert_eq!(count_stones(example1, &RuleSet::standard(), 25, None), Ok(55312));
+        assert_eq!(format!("{}", count_stones(example1, &RuleSet::standard(), 75, None).unwrap()), part2(&example1.to_string()));
+        assert_eq!(count_stones(example1, &RuleSet::standard(), 75, Some(1_000_000_007)), Ok(count_stones(example1, &RuleSet::standard(), 75, None).unwrap() % 1_000_000_007));
+    }
+
+    #[test]
+    fn test_fast_forward() {
+        let example1 = r#"125 17"#;
+
+        let graph = StoneGraph::build(&[125, 17], &RuleSet::standard()).unwrap();
+        for blinks in [500, 501, 2000] {
+            let stepped = graph.get_totals(&[125, 17], blinks, &Counting { modulus: Some(998_244_353 * 4) }).unwrap()[blinks as usize];
+            assert_eq!(count_stones(example1, &RuleSet::standard(), blinks, Some(998_244_353)), Ok(stepped % 998_244_353));
+            assert_eq!(count_stones(example1, &RuleSet::standard(), blinks, Some(998_244_353 * 2)), Ok(stepped % (998_244_353 * 2)));
+        }
+
+        let example2 = &std::fs::read_to_string("day11.txt").expect("Unable to read input file");
+        let stones: Vec<u64> = example2.split_whitespace().map(|stone| stone.parse::<u64>().unwrap()).collect();
+        let stepped = StoneGraph::build(&stones, &RuleSet::standard()).unwrap()
+            .get_totals(&stones, 9000, &Counting { modulus: Some(1_000_000_007 * 6) }).unwrap()[9000];
+        assert_eq!(count_stones(example2, &RuleSet::standard(), 9000, Some(1_000_000_007)), Ok(stepped % 1_000_000_007));
+        assert_eq!(count_stones(example2, &RuleSet::standard(), 9000, Some(1_000_000_007 * 6)), Ok(stepped));
+
+        assert!(count_stones(example1, &RuleSet::standard(), 1_000_000, Some(998_244_353 * 4)).is_err());
+        assert_eq!(count_stones(example1, &RuleSet::standard(), 1_000_000, Some(1)), Ok(0));
+        assert!(count_stones(example1, &RuleSet::standard(), 1_000_000, Some(0)).is_err());
+        assert_eq!(count_stones(example1, &RuleSet::standard(), 1_000_000, None), Err("Stone count overflowed u128, count with a modulus instead".to_string()));
+    }
+
+    #[test]
+    fn test_is_prime() {
+        assert!(is_prime(1_000_000_007));
+        assert!(is_prime(998_244_353));
+        assert!(!is_prime(1_000_000_008));
+        assert!(!is_prime(3_215_031_751));
+        assert!(is_prime(2_305_843_009_213_693_951));
+
+        assert_eq!(get_prime_factors(1_000_000_007 * 6), Ok(vec![2, 3, 1_000_000_007]));
+        assert_eq!(get_prime_factors(2_305_843_009_213_693_951), Ok(vec![2_305_843_009_213_693_951]));
+        assert_eq!(get_prime_factors(1), Ok(vec![]));
+        assert!(get_prime_factors(998_244_353 * 4).is_err());
+        assert!(get_prime_factors(1_000_000_007 * 998_244_353).is_err());
+    }
+
+    #[test]
+    fn test_count_stones_actual() {
+        let example1 = &std::fs::read_to_string("day11.txt").expect("Unable to read input file");
+
+        assert_eq!(part1(example1), "203953".to_string());
+        assert_eq!(part2(example1), "242090118578155".to_string());
+        assert_eq!(count_stones(example1, &RuleSet::standard(), 0, None), Ok(example1.split_whitespace().count() as u128));
+    }
 }
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 struct Position {
     row: i32,
     col: i32,
@@ -44,8 +47,8 @@ fn evaluate_position(map: &Vec<Vec<char>>, search_map: &mut Vec<Vec<bool>>, row:
     }
 }
 
-fn get_region_area(map: &Vec<Vec<char>>, search_map: &mut Vec<Vec<bool>>, row: i32, col: i32, perimeter: &mut Perimeter) -> i32 {
-    let mut area = 0;
+fn get_region(map: &Vec<Vec<char>>, search_map: &mut Vec<Vec<bool>>, row: i32, col: i32, perimeter: &mut Perimeter) -> Vec<Position> {
+    let mut cells: Vec<Position> = Vec::new();
     let region = map[row as usize][col as usize];
 
     let mut queue: Vec<Position> = Vec::new();
@@ -57,7 +60,7 @@ fn get_region_area(map: &Vec<Vec<char>>, search_map: &mut Vec<Vec<bool>>, row: i
         match queue.pop() {
             None => break,
             Some(position) => {
-                area += 1;
+                cells.push(position);
 
                 evaluate_position(map, search_map, position.row + 1, position.col, region, &mut queue, perimeter);
                 evaluate_position(map, search_map, position.row, position.col + 1, region, &mut queue, perimeter);
@@ -67,7 +70,261 @@ fn get_region_area(map: &Vec<Vec<char>>, search_map: &mut Vec<Vec<bool>>, row: i
         }
     }
 
-    area
+    cells
+}
+
+fn get_region_area(map: &Vec<Vec<char>>, search_map: &mut Vec<Vec<bool>>, row: i32, col: i32, perimeter: &mut Perimeter) -> i32 {
+    get_region(map, search_map, row, col, perimeter).len() as i32
+}
+
+#[derive(Debug, PartialEq)]
+struct BoundingBox {
+    top: i32,
+    left: i32,
+    bottom: i32,
+    right: i32
+}
+
+struct Region {
+    id: usize,
+    label: char,
+    cells: Vec<Position>,
+    area: i32,
+    perimeter: i32,
+    sides: i32,
+    bounding_box: BoundingBox,
+    holes: usize,
+    encloses: Vec<usize>
+}
+
+// Everything that isn't part of the region is flooded (8-connected, as the region itself is 4-connected) within
+// the bounding box plus a one cell margin. Anything the flood from the margin can't reach is a hole.
+fn find_holes(region_ids: &[Vec<usize>], region: &Region) -> (usize, Vec<usize>) {
+    let top = region.bounding_box.top - 1;
+    let left = region.bounding_box.left - 1;
+    let height = (region.bounding_box.bottom - top + 2) as usize;
+    let width = (region.bounding_box.right - left + 2) as usize;
+    let is_background = |row: usize, col: usize| {
+        let map_row = row as i32 + top;
+        let map_col = col as i32 + left;
+        map_row < 0 || map_col < 0 || map_row as usize >= region_ids.len() || map_col as usize >= region_ids[0].len()
+            || region_ids[map_row as usize][map_col as usize] != region.id
+    };
+
+    let mut visited = vec![vec![false; width]; height];
+    let mut holes = 0;
+    let mut enclosed: Vec<usize> = Vec::new();
+    for start_row in 0..height {
+        for start_col in 0..width {
+            if visited[start_row][start_col] || !is_background(start_row, start_col) {
+                continue;
+            }
+
+            let mut is_outside = false;
+            let mut members: Vec<(usize, usize)> = Vec::new();
+            let mut queue = vec![(start_row, start_col)];
+            visited[start_row][start_col] = true;
+            while let Some((row, col)) = queue.pop() {
+                members.push((row, col));
+                is_outside |= row == 0 || col == 0 || row == height - 1 || col == width - 1;
+                for row_delta in -1..=1 {
+                    for col_delta in -1..=1 {
+                        let next_row = row as i32 + row_delta;
+                        let next_col = col as i32 + col_delta;
+                        if next_row < 0 || next_col < 0 || next_row as usize >= height || next_col as usize >= width {
+                            continue;
+                        }
+                        let (next_row, next_col) = (next_row as usize, next_col as usize);
+                        if !visited[next_row][next_col] && is_background(next_row, next_col) {
+                            visited[next_row][next_col] = true;
+                            queue.push((next_row, next_col));
+                        }
+                    }
+                }
+            }
+
+            if !is_outside {
+                holes += 1;
+                for (row, col) in members {
+                    let id = region_ids[(row as i32 + top) as usize][(col as i32 + left) as usize];
+                    if !enclosed.contains(&id) {
+                        enclosed.push(id);
+                    }
+                }
+            }
+        }
+    }
+
+    enclosed.sort();
+    (holes, enclosed)
+}
+
+fn analyse_regions(input: &str) -> Vec<Region> {
+    let size = input.lines().count();
+    let map: Vec<Vec<char>> = input.lines().map(|line| line.chars().collect()).collect();
+    let mut search_map: Vec<Vec<bool>> = vec![vec![false; size]; size];
+    let mut region_ids: Vec<Vec<usize>> = vec![vec![0; size]; size];
+    let mut regions: Vec<Region> = Vec::new();
+
+    for row in 0..map.len() {
+        for col in 0..map.len() {
+            if !search_map[row][col] {
+                let mut perimeter = Perimeter { length: 0, sides: 0 };
+                let cells = get_region(&map, &mut search_map, row as i32, col as i32, &mut perimeter);
+                let bounding_box = BoundingBox {
+                    top: cells.iter().map(|cell| cell.row).min().unwrap(),
+                    left: cells.iter().map(|cell| cell.col).min().unwrap(),
+                    bottom: cells.iter().map(|cell| cell.row).max().unwrap(),
+                    right: cells.iter().map(|cell| cell.col).max().unwrap(),
+                };
+                for cell in &cells {
+                    region_ids[cell.row as usize][cell.col as usize] = regions.len();
+                }
+                regions.push(Region {
+                    id: regions.len(),
+                    label: map[row][col],
+                    area: cells.len() as i32,
+                    cells,
+                    perimeter: perimeter.length,
+                    sides: perimeter.sides,
+                    bounding_box,
+                    holes: 0,
+                    encloses: Vec::new()
+                });
+            }
+        }
+    }
+
+    for region in &mut regions {
+        let (holes, encloses) = find_holes(&region_ids, region);
+        region.holes = holes;
+        region.encloses = encloses;
+    }
+
+    regions
+}
+
+fn regions_to_csv(regions: &[Region]) -> String {
+    let mut csv = "id,label,area,perimeter,sides,top,left,bottom,right,holes,encloses".to_string();
+    for region in regions {
+        csv.push_str(&format!("\n{},{},{},{},{},{},{},{},{},{},{}", region.id, region.label, region.area, region.perimeter, region.sides,
+                              region.bounding_box.top, region.bounding_box.left, region.bounding_box.bottom, region.bounding_box.right,
+                              region.holes, region.encloses.iter().map(ToString::to_string).collect::<Vec<String>>().join(" ")));
+    }
+    csv
+}
+
+fn regions_to_json(regions: &[Region]) -> String {
+    let mut objects: Vec<String> = Vec::new();
+    for region in regions {
+        let label = match region.label {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            label => label.to_string(),
+        };
+        let cells = region.cells.iter().map(|cell| format!("[{},{}]", cell.row, cell.col)).collect::<Vec<String>>().join(",");
+        let encloses = region.encloses.iter().map(ToString::to_string).collect::<Vec<String>>().join(",");
+        objects.push(format!("{{\"id\":{},\"label\":\"{}\",\"area\":{},\"perimeter\":{},\"sides\":{},\"bounding_box\":{{\"top\":{},\"left\":{},\"bottom\":{},\"right\":{}}},\"holes\":{},\"encloses\":[{}],\"cells\":[{}]}}",
+                             region.id, label, region.area, region.perimeter, region.sides,
+                             region.bounding_box.top, region.bounding_box.left, region.bounding_box.bottom, region.bounding_box.right,
+                             region.holes, encloses, cells));
+    }
+    format!("[{}]", objects.join(",\n"))
+}
+
+struct GardenRegion {
+    area: i32,
+    perimeter: i32,
+    sides: i32
+}
+
+struct Garden {
+    map: Vec<Vec<char>>,
+    search_map: Vec<Vec<bool>>,
+    region_ids: Vec<Vec<usize>>,
+    regions: HashMap<usize, GardenRegion>,
+    next_region_id: usize,
+    fence_price: i32,
+    bulk_price: i32
+}
+
+impl Garden {
+    fn new(input: &str) -> Garden {
+        let size = input.lines().count();
+        let mut garden = Garden {
+            map: input.lines().map(|line| line.chars().collect()).collect(),
+            search_map: vec![vec![false; size]; size],
+            region_ids: vec![vec![usize::MAX; size]; size],
+            regions: HashMap::new(),
+            next_region_id: 0,
+            fence_price: 0,
+            bulk_price: 0
+        };
+
+        for row in 0..size {
+            for col in 0..size {
+                if garden.region_ids[row][col] == usize::MAX {
+                    garden.add_region(row as i32, col as i32);
+                }
+            }
+        }
+        garden
+    }
+
+    fn add_region(&mut self, row: i32, col: i32) {
+        let mut perimeter = Perimeter { length: 0, sides: 0 };
+        let cells = get_region(&self.map, &mut self.search_map, row, col, &mut perimeter);
+        let id = self.next_region_id;
+        self.next_region_id += 1;
+        for cell in &cells {
+            self.region_ids[cell.row as usize][cell.col as usize] = id;
+            self.search_map[cell.row as usize][cell.col as usize] = false;
+        }
+
+        let region = GardenRegion { area: cells.len() as i32, perimeter: perimeter.length, sides: perimeter.sides };
+        self.fence_price += region.area * region.perimeter;
+        self.bulk_price += region.area * region.sides;
+        self.regions.insert(id, region);
+    }
+
+    fn remove_region(&mut self, id: usize) {
+        if let Some(region) = self.regions.remove(&id) {
+            self.fence_price -= region.area * region.perimeter;
+            self.bulk_price -= region.area * region.sides;
+        }
+    }
+
+    // Only regions with a plot in the 3x3 block around the edit can change: perimeters depend on the four
+    // neighbours and corners on the diagonals too. Every piece of those regions after the edit still touches the
+    // block, so they are removed and flooded again from there while the rest of the garden is left alone.
+    fn set_plot(&mut self, row: i32, col: i32, label: char) {
+        if self.map[row as usize][col as usize] == label {
+            return
+        }
+
+        let size = self.map.len() as i32;
+        let mut neighbourhood: Vec<Position> = Vec::new();
+        for neighbour_row in row - 1..=row + 1 {
+            for neighbour_col in col - 1..=col + 1 {
+                if neighbour_row >= 0 && neighbour_col >= 0 && neighbour_row < size && neighbour_col < size {
+                    neighbourhood.push(Position { row: neighbour_row, col: neighbour_col });
+                }
+            }
+        }
+
+        for position in &neighbourhood {
+            self.remove_region(self.region_ids[position.row as usize][position.col as usize]);
+        }
+
+        self.map[row as usize][col as usize] = label;
+
+        let first_new_id = self.next_region_id;
+        for position in &neighbourhood {
+            if self.region_ids[position.row as usize][position.col as usize] < first_new_id {
+                self.add_region(position.row, position.col);
+            }
+        }
+    }
 }
 
 fn part1(input: &String) -> String {
@@ -114,13 +371,25 @@ fn main() {
 
     println!("Part2: {}", part2(input1));
     // 846196 - too low
+
+    let regions = analyse_regions(input1);
+    if std::env::args().any(|arg| arg == "--json") {
+        println!("{}", regions_to_json(&regions));
+    }
+    if std::env::args().any(|arg| arg == "--csv") {
+        println!("{}", regions_to_csv(&regions));
+    }
+
+    let mut garden = Garden::new(input1);
+    garden.set_plot(0, 0, '#');
+    println!("After fencing off (0, 0): {} regions, {} / {}", garden.regions.len(), garden.fence_price, garden.bulk_price);
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::part1;
-    use crate::part2;
+    use rand::Rng;
+    use crate::{analyse_regions, part1, part2, regions_to_csv, regions_to_json, BoundingBox, Garden};
 
     #[test]
     fn test_part1_small() {
@@ -227,4 +496,114 @@ MMMISSJEEE"#;
 
         assert_eq!(part2(&example1.to_string()), expected1);
     }
+
+    #[test]
+    fn test_analyse_regions() {
+        let example1 = r#"OOOOO
+OXOXO
+OOOOO
+OXOXO
+OOOOO"#;
+
+        let regions = analyse_regions(example1);
+        assert_eq!(regions.len(), 5);
+        let outer = &regions[0];
+        assert_eq!((outer.label, outer.area, outer.perimeter, outer.sides, outer.holes), ('O', 21, 36, 20, 4));
+        assert_eq!(outer.bounding_box, BoundingBox { top: 0, left: 0, bottom: 4, right: 4 });
+        assert_eq!(outer.encloses, vec![1, 2, 3, 4]);
+        assert_eq!(outer.cells.len(), 21);
+        for region in &regions[1..] {
+            assert_eq!((region.label, region.area, region.perimeter, region.sides, region.holes), ('X', 1, 4, 4, 0));
+            assert!(region.encloses.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_nested_regions() {
+        let example1 = r#"AAAAA
+ABBBA
+ABCBA
+ABBBA
+AAAAA"#;
+
+        let regions = analyse_regions(example1);
+        assert_eq!(regions.iter().map(|region| (region.label, region.holes, region.encloses.clone())).collect::<Vec<(char, usize, Vec<usize>)>>(),
+                   vec![('A', 1, vec![1, 2]), ('B', 1, vec![2]), ('C', 0, vec![])]);
+
+        let example2 = r#"AAA.
+A.A.
+AA..
+...."#;
+
+        let regions = analyse_regions(example2);
+        assert_eq!(regions[0].label, 'A');
+        assert_eq!(regions[0].holes, 0);
+        assert_eq!(regions[0].bounding_box, BoundingBox { top: 0, left: 0, bottom: 2, right: 2 });
+    }
+
+    #[test]
+    fn test_region_exports() {
+        let example1 = r#"AAAA
+BBCD
+BBCC
+EEEC"#;
+
+        let regions = analyse_regions(example1);
+        assert_eq!(regions_to_csv(&regions), r#"id,label,area,perimeter,sides,top,left,bottom,right,holes,encloses
+0,A,4,10,4,0,0,0,3,0,
+1,B,4,8,4,1,0,2,1,0,
+2,C,4,10,8,1,2,3,3,0,
+3,D,1,4,4,1,3,1,3,0,
+4,E,3,8,4,3,0,3,2,0,"#);
+        assert!(regions_to_json(&regions[3..4]).starts_with(r#"[{"id":3,"label":"D","area":1,"perimeter":4,"sides":4,"bounding_box":{"top":1,"left":3,"bottom":1,"right":3},"holes":0,"encloses":[],"cells":[[1,3]]}]"#));
+    }
+
+    #[test]
+    fn test_garden_edits() {
+        let example1 = r#"AAAA
+BBCD
+BBCC
+EEEC"#;
+
+        let mut garden = Garden::new(example1);
+        assert_eq!((garden.regions.len(), garden.fence_price, garden.bulk_price), (5, 140, 80));
+
+        garden.set_plot(1, 3, 'C');
+        assert_eq!((garden.regions.len(), garden.fence_price, garden.bulk_price), (4, 4 * 10 + 4 * 8 + 5 * 10 + 3 * 8, 4 * 4 + 4 * 4 + 5 * 6 + 3 * 4));
+
+        garden.set_plot(0, 1, 'C');
+        assert_eq!(garden.regions.len(), 6);
+        assert_eq!(format!("{}", garden.fence_price), part1(&"ACAA\nBBCC\nBBCC\nEEEC".to_string()));
+        assert_eq!(format!("{}", garden.bulk_price), part2(&"ACAA\nBBCC\nBBCC\nEEEC".to_string()));
+    }
+
+    #[test]
+    fn test_garden_random_edits() {
+        let example1 = r#"RRRRIICCFF
+RRRRIICCCF
+VVRRRCCFFF
+VVRCCCJFFF
+VVVVCJJCFE
+VVIVCCJJEE
+VVIIICJJEE
+MIIIIIJJEE
+MIIISIJEEE
+MMMISSJEEE"#;
+
+        let mut map: Vec<Vec<char>> = example1.lines().map(|line| line.chars().collect()).collect();
+        let mut garden = Garden::new(example1);
+        let mut rng = rand::rng();
+        for _ in 0..300 {
+            let row = rng.random_range(0..10);
+            let col = rng.random_range(0..10);
+            let label = ['R', 'I', 'C', 'X'][rng.random_range(0..4)];
+            map[row][col] = label;
+            garden.set_plot(row as i32, col as i32, label);
+
+            let text = map.iter().map(|line| line.iter().collect::<String>()).collect::<Vec<String>>().join("\n");
+            assert_eq!(format!("{}", garden.fence_price), part1(&text));
+            assert_eq!(format!("{}", garden.bulk_price), part2(&text));
+            assert_eq!(garden.regions.len(), analyse_regions(&text).len());
+        }
+    }
 }
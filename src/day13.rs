@@ -1,3 +1,4 @@
+use std::fmt;
 use std::str::FromStr;
 
 struct InputData {
@@ -9,53 +10,329 @@ impl FromStr for InputData {
     type Err = ();
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (x, y) = input[input.find(':').ok_or(())? + 1..].split_once(',').ok_or(())?;
+        let parse = |value: &str| value.trim()[1..].trim_start_matches('=').parse::<i64>().map_err(|_| ());
         Ok(InputData {
-            x: input[input.find('X').unwrap() + 2..input.find(',').unwrap()].parse::<i64>().unwrap(),
-            y: input[input.find('Y').unwrap() + 2..].parse::<i64>().unwrap(),
+            x: parse(x)?,
+            y: parse(y)?,
         })
     }
 }
 
-fn get_score(input: &String, prize_offset: i64) -> String {
-    let mut total_score = 0;
-    let mut lines = input.lines();
+struct Machine {
+    buttons: Vec<InputData>,
+    prize: InputData
+}
+
+fn get_machines(input: &String, prize_offset: i64) -> Vec<Machine> {
+    let mut machines: Vec<Machine> = Vec::new();
+    let mut buttons: Vec<InputData> = Vec::new();
+
+    for line in input.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if line.starts_with("Prize") {
+            let mut prize = line.parse::<InputData>().unwrap();
+            prize.x += prize_offset;
+            prize.y += prize_offset;
+            machines.push(Machine { buttons, prize });
+            buttons = Vec::new();
+        } else {
+            buttons.push(line.parse::<InputData>().unwrap());
+        }
+    }
+
+    machines
+}
+
+fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+    if b == 0 {
+        return if a < 0 { (-a, -1, 0) } else { (a, 1, 0) }
+    }
+    let (gcd, x, y) = extended_gcd(b, a % b);
+    (gcd, y, x - (a / b) * y)
+}
+
+// Ordered from the least to the most promising, so a machine with several buttons reports its closest miss.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Unreachable {
+    CostCount(usize, usize),
+    OffLine,
+    NonInteger,
+    NegativePresses,
+    ExceedsPressLimit(i64),
+    TooManyPresses,
+    NoCheapest,
+    TooManyCombinations
+}
+
+impl fmt::Display for Unreachable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unreachable::CostCount(buttons, costs) => write!(f, "the machine has {} buttons but there are costs for {}", buttons, costs),
+            Unreachable::OffLine => write!(f, "the buttons only move the claw along a line that misses the prize"),
+            Unreachable::NonInteger => write!(f, "the prize needs a non-integer number of presses"),
+            Unreachable::NegativePresses => write!(f, "the prize needs a negative number of presses"),
+            Unreachable::ExceedsPressLimit(limit) => write!(f, "the prize needs more than {} presses of a button", limit),
+            Unreachable::TooManyPresses => write!(f, "the prize needs more presses than can be counted"),
+            Unreachable::NoCheapest => write!(f, "pressing more always costs less, so there's no cheapest way"),
+            Unreachable::TooManyCombinations => write!(f, "there are too many ways to combine the extra buttons to try them all"),
+        }
+    }
+}
+
+// Narrows the range of t so that value + t * shift >= 0 holds for every constraint.
+fn get_bounds(constraints: &[(i128, i128)], mut lower: Option<i128>, mut upper: Option<i128>) -> Option<(Option<i128>, Option<i128>)> {
+    for &(value, shift) in constraints {
+        if shift > 0 {
+            let bound = -value.div_euclid(shift);
+            lower = Some(lower.map_or(bound, |lower| lower.max(bound)));
+        } else if shift < 0 {
+            let bound = value.div_euclid(-shift);
+            upper = Some(upper.map_or(bound, |upper| upper.min(bound)));
+        } else if value < 0 {
+            return None
+        }
+    }
+    if let (Some(lower), Some(upper)) = (lower, upper) {
+        if lower > upper {
+            return None
+        }
+    }
+    Some((lower, upper))
+}
+
+// Cheapest non-negative (a, b) with a * step_a + b * step_b == target, one dimensional.
+fn solve_line(step_a: i64, step_b: i64, target: i64, cost_a: i64, cost_b: i64, press_limit: Option<i64>) -> Result<(i64, i64), Unreachable> {
+    if step_a == 0 && step_b == 0 {
+        return if target == 0 { Ok((0, 0)) } else { Err(Unreachable::OffLine) }
+    }
+
+    let (gcd, x, y) = extended_gcd(step_a, step_b);
+    if target % gcd != 0 {
+        return Err(Unreachable::NonInteger)
+    }
+
+    // Every solution is (a0 + t * shift_a, b0 - t * shift_b), so both press counts being non-negative bounds t.
+    let a0 = x as i128 * (target / gcd) as i128;
+    let b0 = y as i128 * (target / gcd) as i128;
+    let shift_a = (step_b / gcd) as i128;
+    let shift_b = (step_a / gcd) as i128;
+
+    let presses = pick_presses(&[(a0, shift_a), (b0, -shift_b)], &[cost_a, cost_b], press_limit)?;
+    Ok((presses[0], presses[1]))
+}
+
+// Press counts of the form value + t * shift for a whole number t. Picks the t with every count non-negative and
+// within the limit that costs the least.
+fn pick_presses(presses: &[(i128, i128)], costs: &[i64], press_limit: Option<i64>) -> Result<Vec<i64>, Unreachable> {
+    let (mut lower, mut upper) = get_bounds(presses, None, None).ok_or(Unreachable::NegativePresses)?;
+    if let Some(limit) = press_limit {
+        let remaining: Vec<(i128, i128)> = presses.iter().map(|(value, shift)| (limit as i128 - value, -shift)).collect();
+        (lower, upper) = get_bounds(&remaining, lower, upper).ok_or(Unreachable::ExceedsPressLimit(limit))?;
+    }
+
+    let slope: i128 = presses.iter().zip(costs).map(|((_, shift), cost)| shift * *cost as i128).sum();
+    let t = match slope {
+        0 => lower.or(upper).unwrap_or(0),
+        slope if slope > 0 => lower.ok_or(Unreachable::NoCheapest)?,
+        _ => upper.ok_or(Unreachable::NoCheapest)?,
+    };
+    presses.iter().map(|(value, shift)| i64::try_from(value + t * shift).map_err(|_| Unreachable::TooManyPresses)).collect()
+}
+
+// Every t with t * factor = value (mod modulus), as t = remainder (mod step).
+fn solve_congruence(factor: i128, value: i128, modulus: i128) -> Option<(i128, i128)> {
+    let (factor, value) = (factor.rem_euclid(modulus), value.rem_euclid(modulus));
+    let (gcd, inverse, _) = extended_gcd(factor as i64, modulus as i64);
+    if value % gcd as i128 != 0 {
+        return None
+    }
+    let step = modulus / gcd as i128;
+    Some(((value / gcd as i128 * inverse as i128).rem_euclid(step), step))
+}
+
+fn solve_pair(button_a: &InputData, button_b: &InputData, prize: &InputData, cost_a: i64, cost_b: i64, press_limit: Option<i64>) -> Result<(i64, i64), Unreachable> {
+    let determinant = button_a.x * button_b.y - button_a.y * button_b.x;
+    if determinant != 0 {
+        let a_numerator = prize.x * button_b.y - prize.y * button_b.x;
+        let b_numerator = button_a.x * prize.y - button_a.y * prize.x;
+        if a_numerator % determinant != 0 || b_numerator % determinant != 0 {
+            return Err(Unreachable::NonInteger)
+        }
+        let a_presses = a_numerator / determinant;
+        let b_presses = b_numerator / determinant;
+        if a_presses < 0 || b_presses < 0 {
+            return Err(Unreachable::NegativePresses)
+        }
+        if let Some(limit) = press_limit.filter(|&limit| a_presses > limit || b_presses > limit) {
+            return Err(Unreachable::ExceedsPressLimit(limit))
+        }
+        return Ok((a_presses, b_presses))
+    }
+
+    // Both buttons move the claw along the same line, so the prize has to be on it too and what's left is a single
+    // linear Diophantine equation along that line.
+    let direction = if button_a.x != 0 || button_a.y != 0 { button_a } else { button_b };
+    if direction.x == 0 && direction.y == 0 {
+        return if prize.x == 0 && prize.y == 0 { Ok((0, 0)) } else { Err(Unreachable::OffLine) }
+    }
+    if direction.x * prize.y - direction.y * prize.x != 0 {
+        return Err(Unreachable::OffLine)
+    }
+
+    let gcd = extended_gcd(direction.x, direction.y).0;
+    let (unit_x, unit_y) = (direction.x / gcd, direction.y / gcd);
+    let along = |vector: &InputData| if unit_x != 0 { vector.x / unit_x } else { vector.y / unit_y };
+    if along(prize) * unit_x != prize.x || along(prize) * unit_y != prize.y {
+        return Err(Unreachable::NonInteger)
+    }
+    solve_line(along(button_a), along(button_b), along(prize), cost_a, cost_b, press_limit)
+}
+
+// With a third button pressed t times, the first two make up the rest exactly, which needs t to be in the right
+// residue class for both of them to come out whole. So the presses are all linear in a whole number and the cheapest
+// comes straight from the bounds, however far away the prize is.
+fn solve_three(machine: &Machine, costs: &[i64], press_limit: Option<i64>) -> Option<Result<(Vec<i64>, i64), Unreachable>> {
+    let buttons = &machine.buttons;
+    let (first, second, extra) = [(0, 1, 2), (0, 2, 1), (1, 2, 0)].into_iter()
+        .find(|(first, second, _)| buttons[*first].x * buttons[*second].y != buttons[*first].y * buttons[*second].x)?;
+    let (button_a, button_b, button_c) = (&buttons[first], &buttons[second], &buttons[extra]);
+    let prize = &machine.prize;
 
-    loop {
-        let first_line = match lines.next() {
-            None => break,
-            Some(line) => line
-        };
-        let button_a = (if first_line.is_empty() { lines.next().unwrap() } else { first_line }).parse::<InputData>().unwrap();
-        let button_b = lines.next().unwrap().parse::<InputData>().unwrap();
+    let determinant = (button_a.x * button_b.y - button_a.y * button_b.x) as i128;
+    let a_numerator = (prize.x * button_b.y - prize.y * button_b.x) as i128;
+    let a_factor = (button_c.x * button_b.y - button_c.y * button_b.x) as i128;
+    let b_numerator = (button_a.x * prize.y - button_a.y * prize.x) as i128;
+    let b_factor = (button_a.x * button_c.y - button_a.y * button_c.x) as i128;
 
-        let mut prize = lines.next().unwrap().parse::<InputData>().unwrap();
-        prize.x += prize_offset;
-        prize.y += prize_offset;
+    let (a_remainder, a_step) = match solve_congruence(a_factor, a_numerator, determinant.abs()) {
+        Some(congruence) => congruence,
+        None => return Some(Err(Unreachable::NonInteger)),
+    };
+    // Writing t as a_remainder + k * a_step, the second button then needs k in its own residue class.
+    let (t0, step) = match solve_congruence(b_factor * a_step, b_numerator - b_factor * a_remainder, determinant.abs()) {
+        Some((remainder, b_step)) => (a_remainder + a_step * remainder, a_step * b_step),
+        None => return Some(Err(Unreachable::NonInteger)),
+    };
 
-        if (button_a.x * prize.y - button_a.y * prize.x) % (button_a.x * button_b.y - button_a.y * button_b.x) != 0 {
-            continue;
+    let mut presses = vec![(0, 0); 3];
+    presses[first] = ((a_numerator - t0 * a_factor) / determinant, -step * a_factor / determinant);
+    presses[second] = ((b_numerator - t0 * b_factor) / determinant, -step * b_factor / determinant);
+    presses[extra] = (t0, step);
+
+    Some(pick_presses(&presses, costs, press_limit).map(|presses| {
+        let cost = presses.iter().zip(costs).map(|(presses, cost)| presses * cost).sum();
+        (presses, cost)
+    }))
+}
+
+// Only holds while no button moves the claw backwards.
+fn get_press_limit(machine: &Machine, button: &InputData) -> Option<i64> {
+    if machine.buttons.iter().any(|button| button.x < 0 || button.y < 0) {
+        return None
+    }
+    [(button.x, machine.prize.x), (button.y, machine.prize.y)].iter()
+        .filter(|(step, _)| *step > 0)
+        .map(|(step, target)| target / step)
+        .min()
+        .or(Some(0))
+}
+
+// Most presses of a single extra button that are tried one by one.
+const SEARCH_LIMIT: i64 = 1_000_000;
+
+// Up to three buttons are solved exactly, unless all three are collinear. Beyond that the last button's presses are
+// tried one by one while the rest are solved exactly, which needs a bound on those presses.
+fn solve_machine(machine: &Machine, costs: &[i64], press_limit: Option<i64>) -> Result<(Vec<i64>, i64), Unreachable> {
+    if machine.buttons.len() != costs.len() {
+        return Err(Unreachable::CostCount(machine.buttons.len(), costs.len()))
+    }
+    if machine.buttons.len() == 3 {
+        if let Some(outcome) = solve_three(machine, costs, press_limit) {
+            return outcome
         }
+    }
+
+    match machine.buttons.len() {
+        0 => if machine.prize.x == 0 && machine.prize.y == 0 { Ok((vec![], 0)) } else { Err(Unreachable::OffLine) },
+        1 => solve_pair(&machine.buttons[0], &InputData { x: 0, y: 0 }, &machine.prize, costs[0], 0, press_limit)
+            .map(|(presses, _)| (vec![presses], presses * costs[0])),
+        2 => solve_pair(&machine.buttons[0], &machine.buttons[1], &machine.prize, costs[0], costs[1], press_limit)
+            .map(|(a_presses, b_presses)| (vec![a_presses, b_presses], a_presses * costs[0] + b_presses * costs[1])),
+        _ => {
+            let last = machine.buttons.len() - 1;
+            let limit = match (get_press_limit(machine, &machine.buttons[last]), press_limit) {
+                (Some(limit), Some(press_limit)) => limit.min(press_limit),
+                (limit, press_limit) => limit.or(press_limit).ok_or(Unreachable::TooManyCombinations)?,
+            };
+            if limit > SEARCH_LIMIT {
+                return Err(Unreachable::TooManyCombinations)
+            }
+            let mut best: Result<(Vec<i64>, i64), Unreachable> = Err(Unreachable::OffLine);
 
-        let b_presses = (button_a.x * prize.y - button_a.y * prize.x) / (button_a.x * button_b.y - button_a.y * button_b.x);
+            for presses in 0..=limit {
+                let remaining = Machine {
+                    buttons: machine.buttons[..last].iter().map(|button| InputData { x: button.x, y: button.y }).collect(),
+                    prize: InputData {
+                        x: machine.prize.x - machine.buttons[last].x * presses,
+                        y: machine.prize.y - machine.buttons[last].y * presses
+                    }
+                };
+                match (solve_machine(&remaining, &costs[..last], press_limit), &best) {
+                    (Ok((mut all_presses, cost)), _) => {
+                        let cost = cost + presses * costs[last];
+                        if best.as_ref().is_err() || best.as_ref().is_ok_and(|(_, best_cost)| cost < *best_cost) {
+                            all_presses.push(presses);
+                            best = Ok((all_presses, cost));
+                        }
+                    },
+                    (Err(reason), Err(best_reason)) if reason > *best_reason => best = Err(reason),
+                    _ => {}
+                }
+            }
 
-        if (prize.x - button_b.x * b_presses) % button_a.x != 0 {
-            continue;
+            best
         }
+    }
+}
 
-        let a_presses = (prize.x - button_b.x * b_presses) / button_a.x;
+fn get_report(input: &String, prize_offset: i64, costs: &[i64], press_limit: Option<i64>) -> Vec<Result<(Vec<i64>, i64), Unreachable>> {
+    get_machines(input, prize_offset).iter().map(|machine| solve_machine(machine, costs, press_limit)).collect()
+}
+
+fn report_to_string(report: &[Result<(Vec<i64>, i64), Unreachable>]) -> String {
+    let mut lines: Vec<String> = Vec::new();
 
-        total_score += a_presses * 3 + b_presses;
+    for (index, outcome) in report.iter().enumerate() {
+        lines.push(match outcome {
+            Ok((presses, cost)) => {
+                let presses: Vec<String> = presses.iter().enumerate()
+                    .map(|(button, presses)| format!("{}={}", (b'A' + button as u8) as char, presses))
+                    .collect();
+                format!("Machine {}: {}, {} tokens", index + 1, presses.join(" "), cost)
+            },
+            Err(reason) => format!("Machine {}: unreachable, {}", index + 1, reason),
+        });
     }
 
+    lines.join("\n")
+}
+
+fn get_score(input: &String, prize_offset: i64, costs: &[i64], press_limit: Option<i64>) -> String {
+    let total_score: i64 = get_report(input, prize_offset, costs, press_limit).iter()
+        .filter_map(|outcome| outcome.as_ref().ok())
+        .map(|(_, cost)| cost)
+        .sum();
+
     format!("{}", total_score)
 }
 
+// Only the two buttons of the puzzle have a cost, so machines with any other number of buttons don't score.
 fn part1(input: &String) -> String {
-    get_score(input, 0)
+    get_score(input, 0, &[3, 1], Some(100))
 }
 
 fn part2(input: &String) -> String {
-    get_score(input, 10000000000000)
+    get_score(input, 10000000000000, &[3, 1], None)
 }
 
 fn main() {
@@ -64,13 +341,16 @@ fn main() {
     println!("Part1: {}", part1(input1));
 
     println!("Part2: {}", part2(input1));
+
+    if std::env::args().any(|arg| arg == "--report") {
+        println!("{}", report_to_string(&get_report(input1, 0, &[3, 1], Some(100))));
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::part1;
-    use crate::part2;
+    use crate::{get_machines, get_report, part1, part2, report_to_string, solve_machine, Unreachable};
 
     #[test]
     fn test_part1_small() {
@@ -93,4 +373,155 @@ Prize: X=18641, Y=10279"#;
 
         assert_eq!(part1(&example1.to_string()), expected1);
     }
+
+    #[test]
+    fn test_part2_small() {
+        let example1 = r#"Button A: X+94, Y+34
+Button B: X+22, Y+67
+Prize: X=8400, Y=5400
+
+Button A: X+26, Y+66
+Button B: X+67, Y+21
+Prize: X=12748, Y=12176
+
+Button A: X+17, Y+86
+Button B: X+84, Y+37
+Prize: X=7870, Y=6450
+
+Button A: X+69, Y+23
+Button B: X+27, Y+71
+Prize: X=18641, Y=10279"#;
+        let expected1 = "875318608908".to_string();
+
+        assert_eq!(part2(&example1.to_string()), expected1);
+    }
+
+    #[test]
+    fn test_collinear_buttons() {
+        let example1 = r#"Button A: X+2, Y+2
+Button B: X+4, Y+4
+Prize: X=10, Y=10
+
+Button A: X+2, Y+2
+Button B: X+4, Y+4
+Prize: X=11, Y=11
+
+Button A: X+2, Y+2
+Button B: X+4, Y+4
+Prize: X=10, Y=12
+
+Button A: X+3, Y+0
+Button B: X-2, Y+0
+Prize: X=7, Y=0"#;
+
+        let machines = get_machines(&example1.to_string(), 0);
+        assert_eq!(solve_machine(&machines[0], &[3, 1], None), Ok((vec![1, 2], 5)));
+        assert_eq!(solve_machine(&machines[0], &[1, 3], None), Ok((vec![5, 0], 5)));
+        assert_eq!(solve_machine(&machines[1], &[3, 1], None), Err(Unreachable::NonInteger));
+        assert_eq!(solve_machine(&machines[2], &[3, 1], None), Err(Unreachable::OffLine));
+        assert_eq!(solve_machine(&machines[3], &[3, 1], None), Ok((vec![3, 1], 10)));
+        assert_eq!(solve_machine(&machines[0], &[3, 1], Some(1)), Err(Unreachable::ExceedsPressLimit(1)));
+        assert_eq!(solve_machine(&machines[0], &[1, 3], Some(3)), Ok((vec![3, 1], 6)));
+        assert_eq!(part1(&example1.to_string()), "15".to_string());
+
+        // Collinear buttons going opposite ways, with B cheap enough that more presses always cost less
+        assert_eq!(solve_machine(&machines[3], &[3, -5], None), Err(Unreachable::NoCheapest));
+        assert_eq!(solve_machine(&machines[3], &[3, -5], Some(50)), Ok((vec![35, 49], -140)));
+    }
+
+    #[test]
+    fn test_three_buttons() {
+        let example1 = r#"Button A: X+1, Y+0
+Button B: X+0, Y+1
+Button C: X+1, Y+1
+Prize: X=5, Y=3"#;
+
+        let machines = get_machines(&example1.to_string(), 0);
+        assert_eq!(solve_machine(&machines[0], &[3, 1, 1], None), Ok((vec![2, 0, 3], 9)));
+        assert_eq!(solve_machine(&machines[0], &[1, 1, 5], None), Ok((vec![5, 3, 0], 8)));
+        assert_eq!(solve_machine(&machines[0], &[3, 1, 1], Some(2)), Err(Unreachable::ExceedsPressLimit(2)));
+    }
+
+    #[test]
+    fn test_three_buttons_far_away() {
+        let example1 = r#"Button A: X+94, Y+34
+Button B: X+22, Y+67
+Button C: X-3, Y+5
+Prize: X=10000000008400, Y=10000000005400
+
+Button A: X+2, Y+0
+Button B: X+0, Y+2
+Button C: X+1, Y+1
+Prize: X=10000000000001, Y=10000000000000
+
+Button A: X+1, Y+0
+Button B: X+0, Y+1
+Button C: X-1, Y-1
+Prize: X=2, Y=3
+
+Button A: X+1, Y+0
+Button B: X+0, Y+1
+Button C: X+1, Y+1
+Prize: X=-1, Y=5
+
+Button A: X+1, Y+0
+Button B: X+0, Y+1
+Button C: X+1, Y+1
+Button D: X-1, Y+0
+Prize: X=5, Y=3"#;
+
+        let machines = get_machines(&example1.to_string(), 0);
+        let (presses, cost) = solve_machine(&machines[0], &[3, 1, 1], None).unwrap();
+        assert_eq!(presses.iter().zip([(94, 34), (22, 67), (-3, 5)]).map(|(presses, (x, y))| (presses * x, presses * y))
+            .fold((0, 0), |sum, (x, y)| (sum.0 + x, sum.1 + y)), (10000000008400, 10000000005400));
+        assert_eq!(cost, presses[0] * 3 + presses[1] + presses[2]);
+
+        assert_eq!(solve_machine(&machines[1], &[3, 3, 1], None), Err(Unreachable::NonInteger));
+        assert_eq!(solve_machine(&machines[2], &[1, 1, 1], None), Ok((vec![2, 3, 0], 5)));
+        assert_eq!(solve_machine(&machines[2], &[1, 1, -5], None), Err(Unreachable::NoCheapest));
+        assert_eq!(solve_machine(&machines[2], &[1, 1, -5], Some(10)), Ok((vec![9, 10, 7], -16)));
+        assert_eq!(solve_machine(&machines[3], &[1, 1, 1], None), Err(Unreachable::NegativePresses));
+        assert_eq!(solve_machine(&machines[4], &[1, 1, 1, 1], None), Err(Unreachable::TooManyCombinations));
+        assert_eq!(solve_machine(&machines[4], &[1, 1, 1, 1], Some(10)), Ok((vec![2, 0, 3, 0], 5)));
+    }
+
+    #[test]
+    fn test_report() {
+        let example1 = r#"Button A: X+94, Y+34
+Button B: X+22, Y+67
+Prize: X=8400, Y=5400
+
+Button A: X+26, Y+66
+Button B: X+67, Y+21
+Prize: X=12748, Y=12176
+
+Button A: X+17, Y+86
+Button B: X+84, Y+37
+Prize: X=7870, Y=6450
+
+Button A: X+1, Y+0
+Button B: X+0, Y+1
+Prize: X=-1, Y=2
+
+Button A: X+1, Y+0
+Button B: X+0, Y+1
+Button C: X+1, Y+1
+Prize: X=2, Y=2"#;
+
+        let report = get_report(&example1.to_string(), 0, &[3, 1], Some(100));
+        assert_eq!(report[0], Ok((vec![80, 40], 280)));
+        assert_eq!(report[1], Err(Unreachable::NonInteger));
+        assert_eq!(report[2], Ok((vec![38, 86], 200)));
+        assert_eq!(report[3], Err(Unreachable::NegativePresses));
+        assert_eq!(report[4], Err(Unreachable::CostCount(3, 2)));
+        assert_eq!(part1(&example1.to_string()), "480");
+        assert_eq!(get_report(&example1.to_string(), 0, &[3, 1], Some(50))[0], Err(Unreachable::ExceedsPressLimit(50)));
+
+        let expected1 = r#"Machine 1: A=80 B=40, 280 tokens
+Machine 2: unreachable, the prize needs a non-integer number of presses
+Machine 3: A=38 B=86, 200 tokens
+Machine 4: unreachable, the prize needs a negative number of presses
+Machine 5: unreachable, the machine has 3 buttons but there are costs for 2"#;
+        assert_eq!(report_to_string(&report), expected1);
+    }
 }
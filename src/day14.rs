@@ -1,4 +1,4 @@
-use std::io::stdin;
+use std::collections::{HashMap, HashSet};
 
 struct Robot {
     position_x: i32,
@@ -22,84 +22,186 @@ fn parse_position(line: &str) -> Robot {
     parse_velocity(&line[space + 3..], position_x, position_y)
 }
 
-fn calculate_final_position(initial_position: i32, velocity: i32, size: i32) -> i32 {
-    let remainder = (initial_position + velocity * 100) % size;
-    if remainder.is_negative() { remainder + size } else { remainder }
+fn get_position_after(initial_position: i32, velocity: i32, size: i32, seconds: i64) -> i32 {
+    (initial_position as i64 + velocity as i64 * seconds).rem_euclid(size as i64) as i32
 }
 
-fn part1(input: &String, width: i32, height: i32) -> String {
-    let mut safety_north_west = 0;
-    let mut safety_north_east = 0;
-    let mut safety_south_west = 0;
-    let mut safety_south_east = 0;
-    for line in input.lines() {
-        let robot = parse_position(line);
-        let final_x = calculate_final_position(robot.position_x, robot.velocity_x, width);
-        let final_y = calculate_final_position(robot.position_y, robot.velocity_y, height);
-        if final_x == width / 2 || final_y == height / 2 {
-            continue;
-        }
-        if final_x < width / 2 {
-            if final_y < height / 2 {
-                safety_north_west += 1;
-            } else {
-                safety_south_west += 1;
-            }
-        } else {
-            if final_y < height / 2 {
-                safety_north_east += 1;
-            } else {
-                safety_south_east += 1;
-            }
-        }
+fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+    if b == 0 {
+        return (a, 1, 0)
     }
+    let (gcd, x, y) = extended_gcd(b, a % b);
+    (gcd, y, x - (a / b) * y)
+}
 
-    format!("{}", safety_north_west * safety_north_east * safety_south_west * safety_south_east)
+struct Swarm {
+    robots: Vec<Robot>,
+    width: i32,
+    height: i32
 }
 
-fn part2(input: &String, width: i32, height: i32) {
-    let mut robots: Vec<Robot> = Vec::new();
-    for line in input.lines() {
-        robots.push(parse_position(line));
+impl Swarm {
+    fn parse(input: &str, width: i32, height: i32) -> Swarm {
+        Swarm { robots: input.lines().filter(|line| !line.is_empty()).map(parse_position).collect(), width, height }
+    }
+
+    fn positions_at(&self, seconds: i64) -> Vec<(i32, i32)> {
+        self.robots.iter().map(|robot| (
+            get_position_after(robot.position_x, robot.velocity_x, self.width, seconds),
+            get_position_after(robot.position_y, robot.velocity_y, self.height, seconds)
+        )).collect()
     }
 
-    for seconds in 1..i32::MAX {
-        let is_candidate = (seconds - 14) % 101 == 0;
+    fn period(&self) -> i64 {
+        self.width as i64 / extended_gcd(self.width as i64, self.height as i64).0 * self.height as i64
+    }
 
-        let mut display = Vec::new();
-        if is_candidate {
-            display = vec!(vec!(' '; width as usize); height as usize);
+    // Robots with left <= x < right and top <= y < bottom.
+    fn count_in_region(&self, seconds: i64, left: i32, top: i32, right: i32, bottom: i32) -> usize {
+        self.positions_at(seconds).iter().filter(|(x, y)| *x >= left && *x < right && *y >= top && *y < bottom).count()
+    }
+
+    // Robot counts for a k x k grid of regions, indexed by row then column. Empty unless k is positive.
+    fn partition(&self, seconds: i64, k: i32) -> Vec<Vec<usize>> {
+        if k <= 0 {
+            return Vec::new()
+        }
+        let mut counts = vec!(vec!(0; k as usize); k as usize);
+        for (x, y) in self.positions_at(seconds) {
+            counts[(y * k / self.height) as usize][(x * k / self.width) as usize] += 1;
         }
+        counts
+    }
+
+    fn safety_factor(&self, seconds: i64) -> usize {
+        let (middle_x, middle_y) = (self.width / 2, self.height / 2);
+        self.count_in_region(seconds, 0, 0, middle_x, middle_y)
+            * self.count_in_region(seconds, middle_x + 1, 0, self.width, middle_y)
+            * self.count_in_region(seconds, 0, middle_y + 1, middle_x, self.height)
+            * self.count_in_region(seconds, middle_x + 1, middle_y + 1, self.width, self.height)
+    }
 
-        for robot in &mut robots {
-            robot.position_x += robot.velocity_x;
-            if robot.position_x < 0 {
-                robot.position_x += width;
-            } else if robot.position_x >= width {
-                robot.position_x -= width;
+    fn get_occupancy(&self, seconds: i64) -> HashMap<(i32, i32), usize> {
+        let mut occupancy: HashMap<(i32, i32), usize> = HashMap::new();
+        for position in self.positions_at(seconds) {
+            *occupancy.entry(position).or_insert(0) += 1;
+        }
+        occupancy
+    }
+
+    // Tiles holding more than one robot, with how many robots share them.
+    fn collisions(&self, seconds: i64) -> Vec<((i32, i32), usize)> {
+        let mut collisions: Vec<((i32, i32), usize)> = self.get_occupancy(seconds).into_iter().filter(|(_, count)| *count > 1).collect();
+        collisions.sort();
+        collisions
+    }
+
+    // Groups of orthogonally adjacent occupied tiles with at least min_size tiles, largest first.
+    fn clusters(&self, seconds: i64, min_size: usize) -> Vec<Vec<(i32, i32)>> {
+        let occupancy = self.get_occupancy(seconds);
+        let mut seen: HashSet<(i32, i32)> = HashSet::new();
+        let mut clusters: Vec<Vec<(i32, i32)>> = Vec::new();
+
+        let mut positions: Vec<&(i32, i32)> = occupancy.keys().collect();
+        positions.sort();
+        for &start in positions {
+            if seen.contains(&start) {
+                continue;
             }
-            robot.position_y += robot.velocity_y;
-            if robot.position_y < 0 {
-                robot.position_y += height;
-            } else if robot.position_y >= height {
-                robot.position_y -= height;
+            seen.insert(start);
+            let mut cluster = vec!(start);
+            let mut index = 0;
+            while index < cluster.len() {
+                let (x, y) = cluster[index];
+                for neighbour in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                    if occupancy.contains_key(&neighbour) && !seen.contains(&neighbour) {
+                        seen.insert(neighbour);
+                        cluster.push(neighbour);
+                    }
+                }
+                index += 1;
             }
-
-            if is_candidate {
-                display[robot.position_y as usize][robot.position_x as usize] = '#';
+            if cluster.len() >= min_size {
+                cluster.sort();
+                clusters.push(cluster);
             }
         }
 
-        if is_candidate {
-            println!("\n{} seconds\n", seconds);
-            for line in display.iter() {
-                println!("{}", line.iter().collect::<String>())
-            }
+        clusters.sort_by(|first, second| second.len().cmp(&first.len()).then(first.cmp(second)));
+        clusters
+    }
 
-            let mut s = String::new();
-            stdin().read_line(&mut s).expect("Did not enter a correct string");
+    fn render(&self, seconds: i64) -> String {
+        let mut display = vec!(vec!(' '; self.width as usize); self.height as usize);
+        for (x, y) in self.positions_at(seconds) {
+            display[y as usize][x as usize] = '#';
         }
+        display.iter().map(|line| line.iter().collect::<String>()).collect::<Vec<String>>().join("\n")
+    }
+}
+
+fn part1(input: &String, width: i32, height: i32) -> String {
+    format!("{}", Swarm::parse(input, width, height).safety_factor(100))
+}
+
+fn get_variance(values: impl Iterator<Item=i32>) -> f64 {
+    let (mut count, mut sum, mut sum_of_squares) = (0.0, 0.0, 0.0);
+    for value in values {
+        count += 1.0;
+        sum += value as f64;
+        sum_of_squares += (value as f64) * (value as f64);
+    }
+    if count == 0.0 { 0.0 } else { sum_of_squares / count - (sum / count) * (sum / count) }
+}
+
+// Each axis repeats on its own period, so the picture is the second where the robots bunch up on both axes at once.
+fn get_tightest_second(robots: &[Robot], size: i32, axis: impl Fn(&Robot) -> (i32, i32)) -> i64 {
+    (0..size as i64)
+        .map(|seconds| (seconds, get_variance(robots.iter().map(|robot| {
+            let (position, velocity) = axis(robot);
+            get_position_after(position, velocity, size, seconds)
+        }))))
+        .min_by(|(_, first), (_, second)| first.total_cmp(second))
+        .unwrap()
+        .0
+}
+
+// Smallest non-negative t with t = first (mod first_modulus) and t = second (mod second_modulus).
+fn chinese_remainder(first: i64, first_modulus: i64, second: i64, second_modulus: i64) -> Option<i64> {
+    let (gcd, x, _) = extended_gcd(first_modulus, second_modulus);
+    if (second - first) % gcd != 0 {
+        return None
     }
+    let lcm = first_modulus / gcd * second_modulus;
+    let step = ((second - first) / gcd * x).rem_euclid(second_modulus / gcd);
+    Some((first + first_modulus * step).rem_euclid(lcm))
+}
+
+fn find_easter_egg(input: &String, width: i32, height: i32) -> (i64, String) {
+    let swarm = Swarm::parse(input, width, height);
+
+    let seconds_x = get_tightest_second(&swarm.robots, width, |robot| (robot.position_x, robot.velocity_x));
+    let seconds_y = get_tightest_second(&swarm.robots, height, |robot| (robot.position_y, robot.velocity_y));
+    let period = swarm.period();
+
+    // When the periods share a factor the two best seconds may disagree, so fall back to scoring every frame.
+    let seconds = chinese_remainder(seconds_x, width as i64, seconds_y, height as i64).unwrap_or_else(|| {
+        (0..period)
+            .map(|seconds| {
+                let positions = swarm.positions_at(seconds);
+                (seconds, get_variance(positions.iter().map(|(x, _)| *x)) + get_variance(positions.iter().map(|(_, y)| *y)))
+            })
+            .min_by(|(_, first), (_, second)| first.total_cmp(second))
+            .unwrap()
+            .0
+    });
+    let seconds = if seconds == 0 { period } else { seconds };
+
+    (seconds, swarm.render(seconds))
+}
+
+fn part2(input: &String, width: i32, height: i32) -> String {
+    format!("{}", find_easter_egg(input, width, height).0)
 }
 
 fn main() {
@@ -107,14 +209,21 @@ fn main() {
     let input1 = &std::fs::read_to_string("day14.txt").expect("Unable to read input file");
     println!("Part1: {}", part1(input1, 101, 103));
 
-    part2(input1, 101, 103);
+    println!("Part2: {}", part2(input1, 101, 103));
+    let (seconds, frame) = find_easter_egg(input1, 101, 103);
+    println!("{}", frame);
+
+    let swarm = Swarm::parse(input1, 101, 103);
+    println!("Period: {}", swarm.period());
+    println!("Collisions after 100 seconds: {}", swarm.collisions(100).len());
+    println!("Density after 100 seconds: {:?}", swarm.partition(100, 3));
+    println!("Largest cluster at {} seconds: {}", seconds, swarm.clusters(seconds, 1).first().map_or(0, |cluster| cluster.len()));
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::part1;
-    use crate::part2;
+    use crate::{chinese_remainder, find_easter_egg, part1, part2, Swarm};
 
     #[test]
     fn test_part1() {
@@ -150,6 +259,80 @@ p=7,3 v=-1,2
 p=2,4 v=2,-3
 p=9,5 v=-3,-3"#;
 
-        part2(&example1.to_string(), 11, 7);
+        let (seconds, frame) = find_easter_egg(&example1.to_string(), 11, 7);
+        assert_eq!(part2(&example1.to_string(), 11, 7), format!("{}", seconds));
+        assert!(seconds > 0 && seconds <= 77);
+        assert_eq!(frame.lines().count(), 7);
+        assert!(frame.matches('#').count() <= 12);
+    }
+
+    #[test]
+    fn test_part2_picture() {
+        // A 10x6 block drawn at second 1234, with the remaining robots scattered
+        let (width, height, seconds): (i32, i32, i32) = (41, 43, 1234);
+        let mut state: u64 = 12345;
+        let mut random = |modulus: i32| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((state >> 33) % modulus as u64) as i32
+        };
+        let mut lines: Vec<String> = Vec::new();
+        for index in 0..120 {
+            let velocity_x = random(19) - 9;
+            let velocity_y = random(17) - 8;
+            let (x, y) = if index < 60 {
+                ((15 + index % 10 - velocity_x * seconds).rem_euclid(width), (20 + index / 10 - velocity_y * seconds).rem_euclid(height))
+            } else {
+                (random(width), random(height))
+            };
+            lines.push(format!("p={},{} v={},{}", x, y, velocity_x, velocity_y));
+        }
+
+        let (found, frame) = find_easter_egg(&lines.join("\n"), width, height);
+        assert_eq!(found, seconds as i64);
+        assert_eq!(frame.lines().nth(22).unwrap()[15..25], *"##########");
+    }
+
+    #[test]
+    fn test_chinese_remainder() {
+        assert_eq!(chinese_remainder(2, 3, 3, 5), Some(8));
+        assert_eq!(chinese_remainder(1, 4, 3, 6), Some(9));
+        assert_eq!(chinese_remainder(1, 4, 2, 6), None);
+    }
+
+    #[test]
+    fn test_swarm() {
+        let example1 = r#"p=0,4 v=3,-3
+p=6,3 v=-1,-3
+p=10,3 v=-1,2
+p=2,0 v=2,-1
+p=0,0 v=1,3
+p=3,0 v=-2,-2
+p=7,6 v=-1,-3
+p=3,0 v=-1,-2
+p=9,3 v=2,3
+p=7,3 v=-1,2
+p=2,4 v=2,-3
+p=9,5 v=-3,-3"#;
+        let expected1 = r#"      #  # 
+           
+#          
+ ##        
+     #     
+   ##      
+ #    #    "#;
+
+        let swarm = Swarm::parse(example1, 11, 7);
+        assert_eq!(swarm.render(100), expected1);
+        assert_eq!(swarm.period(), 77);
+        assert_eq!(swarm.positions_at(100 + 77 * 1000000), swarm.positions_at(100));
+        assert_eq!(swarm.collisions(100), vec!(((4, 5), 2), ((6, 0), 2)));
+        assert_eq!(swarm.count_in_region(100, 0, 0, 11, 7), 12);
+        assert_eq!(swarm.count_in_region(100, 3, 4, 6, 6), 4);
+        assert_eq!(swarm.partition(100, 2), vec!(vec!(3, 3), vec!(5, 1)));
+        assert!(swarm.partition(100, 0).is_empty());
+        assert!(swarm.partition(100, -3).is_empty());
+        assert_eq!(swarm.safety_factor(100), 12);
+        assert_eq!(swarm.clusters(100, 2), vec!(vec!((1, 3), (2, 3)), vec!((3, 5), (4, 5))));
+        assert_eq!(swarm.clusters(100, 1).len(), 8);
     }
 }
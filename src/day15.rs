@@ -1,216 +1,316 @@
 use std::cmp::PartialEq;
+use std::fmt;
+use std::io::{BufRead, Write};
 
-#[derive(PartialEq, Clone)]
-enum Entity {
-    Wall,
-    Box,
-    Space,
-    BoxLeft,
-    BoxRight
+// A box covers any set of cells: 'O' is a single cell, '[' to ']' a horizontal run, and a lowercase letter the
+// orthogonally connected cells carrying that letter, which allows tall or L-shaped boxes.
+#[derive(Clone, PartialEq, Debug)]
+struct Crate {
+    cells: Vec<(i32, i32)>,
+    label: char
 }
 
-fn push(map: &mut Vec<Vec<Entity>>, map_width: i32, position_x: i32, position_y: i32, direction_x: i32, direction_y: i32, entity: Entity) -> bool {
-    let destination_x = position_x + direction_x;
-    let destination_y = position_y + direction_y;
-    if destination_x < 0 || destination_y < 0 || destination_x > map_width || destination_y > map.len() as i32 {
-        return false
+fn get_direction(instruction: char) -> (i32, i32) {
+    match instruction {
+        '<' => (-1, 0),
+        '>' => (1, 0),
+        '^' => (0, -1),
+        'v' => (0, 1),
+        _ => (0, 0),
     }
-    let success = match map[destination_y as usize][destination_x as usize] {
-        Entity::Wall => false,
-        Entity::Box => {
-            push(map, map_width, destination_x, destination_y, direction_x, direction_y, Entity::Box)
-        },
-        Entity::BoxLeft => {
-            push(map, map_width, destination_x, destination_y, direction_x, direction_y, Entity::BoxLeft)
-        },
-        Entity::BoxRight => {
-            push(map, map_width, destination_x, destination_y, direction_x, direction_y, Entity::BoxRight)
-        },
-        Entity::Space => {
-            true
-        },
-    };
-    if success {
-        map[destination_y as usize][destination_x as usize] = entity;
-    }
-    success
 }
 
-fn part1(input: &String) -> String {
-    let mut map: Vec<Vec<Entity>> = Vec::new();
-    let mut robot_x: i32 = 0;
-    let mut robot_y: i32 = 0;
-
-    let mut row_index = 0;
+// The map is everything up to the first blank line, the moves are the rest with line breaks dropped.
+fn split_input(input: &str) -> (String, String) {
     let mut lines = input.lines();
-    loop {
-        let mut col_index = 0;
-        let line = lines.next().unwrap();
-        if line.is_empty() {
-            break;
-        }
-        let mut row: Vec<Entity> = Vec::new();
-        for entity in line.chars() {
-            row.push(match entity {
-                '#' => Entity::Wall,
-                'O' => Entity::Box,
-                '@' => {
-                    robot_x = col_index;
-                    robot_y = row_index;
-                    Entity::Space
-                },
-                _ => Entity::Space,
-            });
-            col_index += 1;
+    let map: Vec<&str> = lines.by_ref().take_while(|line| !line.is_empty()).collect();
+    (map.join("\n"), lines.collect::<String>())
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Warehouse {
+    walls: Vec<Vec<bool>>,
+    crates: Vec<Crate>,
+    occupancy: Vec<Vec<Option<usize>>>,
+    robot_x: i32,
+    robot_y: i32
+}
+
+impl Warehouse {
+    // Wide warehouses double every tile, so boxes become '[]' and a lettered box twice as wide.
+    fn parse(map_text: &str, wide: bool) -> Warehouse {
+        let grid: Vec<Vec<char>> = map_text.lines().take_while(|line| !line.is_empty()).map(|line| {
+            if !wide {
+                return line.chars().collect()
+            }
+            line.chars().flat_map(|tile| match tile {
+                'O' => ['[', ']'],
+                '@' => ['@', '.'],
+                tile => [tile, tile],
+            }).collect()
+        }).collect();
+        let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+
+        let mut walls = vec!(vec!(false; width); grid.len());
+        let mut occupancy: Vec<Vec<Option<usize>>> = vec!(vec!(None; width); grid.len());
+        let mut crates: Vec<Crate> = Vec::new();
+        let mut robot_x: i32 = 0;
+        let mut robot_y: i32 = 0;
+
+        for (row_index, row) in grid.iter().enumerate() {
+            for (col_index, tile) in row.iter().enumerate() {
+                if occupancy[row_index][col_index].is_some() {
+                    continue;
+                }
+                let cells: Vec<(i32, i32)> = match tile {
+                    '#' => { walls[row_index][col_index] = true; continue; },
+                    '@' => { robot_x = col_index as i32; robot_y = row_index as i32; continue; },
+                    'O' => vec![(col_index as i32, row_index as i32)],
+                    '[' => {
+                        let length = row[col_index..].iter().position(|tile| *tile == ']').expect("Unterminated box");
+                        (col_index..=col_index + length).map(|col| (col as i32, row_index as i32)).collect()
+                    },
+                    tile if tile.is_ascii_lowercase() => {
+                        let mut cells = vec![(col_index as i32, row_index as i32)];
+                        let mut index = 0;
+                        while index < cells.len() {
+                            let (x, y) = cells[index];
+                            for (next_x, next_y) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                                let is_same = next_y >= 0 && next_x >= 0 && grid.get(next_y as usize)
+                                    .and_then(|row| row.get(next_x as usize)) == Some(tile);
+                                if is_same && !cells.contains(&(next_x, next_y)) {
+                                    cells.push((next_x, next_y));
+                                }
+                            }
+                            index += 1;
+                        }
+                        cells
+                    },
+                    _ => continue,
+                };
+                for (x, y) in cells.iter() {
+                    occupancy[*y as usize][*x as usize] = Some(crates.len());
+                }
+                crates.push(Crate { cells, label: *tile });
+            }
         }
-        map.push(row);
 
-        row_index += 1;
+        Warehouse { walls, crates, occupancy, robot_x, robot_y }
     }
-    let size = map.len();
-
-    for instruction in lines.collect::<String>().chars() {
-        let mut direction_x: i32 = 0;
-        let mut direction_y: i32 = 0;
-        match instruction {
-            '<' => { direction_x = -1; }
-            '>' => { direction_x = 1; }
-            '^' => { direction_y = -1; }
-            'v' => { direction_y = 1; }
-            _ => {}
-        }
-        if push(&mut map, size as i32, robot_x, robot_y, direction_x, direction_y, Entity::Space) {
-            robot_x += direction_x;
-            robot_y += direction_y;
+
+    fn is_wall(&self, x: i32, y: i32) -> bool {
+        x < 0 || y < 0 || y as usize >= self.walls.len() || x as usize >= self.walls[y as usize].len() || self.walls[y as usize][x as usize]
+    }
+
+    // First phase of a push: every box the robot would shove along, or None if any of them runs into a wall.
+    fn get_pushed_crates(&self, direction_x: i32, direction_y: i32) -> Option<Vec<usize>> {
+        let mut pushed: Vec<usize> = Vec::new();
+        let mut frontier: Vec<(i32, i32)> = vec![(self.robot_x + direction_x, self.robot_y + direction_y)];
+
+        while let Some((x, y)) = frontier.pop() {
+            if self.is_wall(x, y) {
+                return None
+            }
+            if let Some(index) = self.occupancy[y as usize][x as usize] {
+                if !pushed.contains(&index) {
+                    pushed.push(index);
+                    frontier.extend(self.crates[index].cells.iter().map(|(x, y)| (x + direction_x, y + direction_y)));
+                }
+            }
         }
+
+        Some(pushed)
     }
 
-    let mut coordinate_sum = 0;
-    for height in 0..size {
-        for width in 0..size {
-            if map[height][width] == Entity::Box {
-                coordinate_sum += height * 100 + width;
+    fn move_robot(&mut self, instruction: char) -> bool {
+        let (direction_x, direction_y) = get_direction(instruction);
+        if direction_x == 0 && direction_y == 0 {
+            return false
+        }
+
+        let pushed = match self.get_pushed_crates(direction_x, direction_y) {
+            None => return false,
+            Some(pushed) => pushed,
+        };
+
+        // Second phase, every box is lifted off the map before any is put back down so the chain can't overwrite itself.
+        for index in pushed.iter() {
+            for (x, y) in self.crates[*index].cells.iter() {
+                self.occupancy[*y as usize][*x as usize] = None;
             }
         }
+        for index in pushed.iter() {
+            for (x, y) in self.crates[*index].cells.iter_mut() {
+                *x += direction_x;
+                *y += direction_y;
+                self.occupancy[*y as usize][*x as usize] = Some(*index);
+            }
+        }
+        self.robot_x += direction_x;
+        self.robot_y += direction_y;
+        true
     }
 
-    format!("{}", coordinate_sum)
-}
+    // Top left corner of every box with its GPS coordinate, in the order the boxes were read.
+    fn get_coordinates(&self) -> Vec<((i32, i32), usize)> {
+        self.crates.iter().map(|item| {
+            let top = item.cells.iter().map(|(_, y)| *y).min().unwrap();
+            let left = item.cells.iter().map(|(x, _)| *x).min().unwrap();
+            ((left, top), top as usize * 100 + left as usize)
+        }).collect()
+    }
 
-fn push_wide(map: &mut Vec<Vec<Entity>>, position_x: i32, position_y: i32, direction_y: i32, entity: Entity, dry_run: bool) -> bool {
-    let destination_y = position_y + direction_y;
-    if destination_y < 0 || destination_y > map.len() as i32 {
-        return false
+    fn get_coordinate_sum(&self) -> usize {
+        self.get_coordinates().iter().map(|(_, coordinate)| coordinate).sum()
     }
-    let success = match map[destination_y as usize][position_x as usize] {
-        Entity::Wall => false,
-        Entity::BoxLeft => {
-            push_wide(map, position_x, destination_y, direction_y, Entity::BoxLeft, dry_run) && push_wide(map, position_x + 1, destination_y, direction_y, Entity::BoxRight, dry_run)
-        },
-        Entity::BoxRight => {
-            push_wide(map, position_x, destination_y, direction_y, Entity::BoxRight, dry_run) && push_wide(map, position_x - 1, destination_y, direction_y, Entity::BoxLeft, dry_run)
-        }
-        Entity::Space => {
-            true
-        },
-        _ => false,
-    };
-    if success && !dry_run {
-        map[destination_y as usize][position_x as usize] = entity;
-        match map[destination_y as usize][position_x as usize] {
-            Entity::BoxLeft => {
-                map[position_y as usize][position_x as usize] = Entity::Space;
-                map[position_y as usize][position_x as usize + 1] = Entity::Space;
-            },
-            Entity::BoxRight => {
-                map[position_y as usize][position_x as usize] = Entity::Space;
-                map[position_y as usize][position_x as usize - 1] = Entity::Space;
+
+    // Compares against a map drawn the same way, ignoring surrounding whitespace so it can be indented in tests.
+    fn matches(&self, expected: &str) -> bool {
+        let expected: Vec<&str> = expected.trim().lines().map(str::trim).collect();
+        self.to_string().lines().eq(expected)
+    }
+}
+
+impl fmt::Display for Warehouse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut display: Vec<Vec<char>> = self.walls.iter()
+            .map(|row| row.iter().map(|wall| if *wall { '#' } else { '.' }).collect())
+            .collect();
+        for item in self.crates.iter() {
+            let left = item.cells.iter().map(|(x, _)| *x).min().unwrap();
+            let right = item.cells.iter().map(|(x, _)| *x).max().unwrap();
+            for (x, y) in item.cells.iter() {
+                display[*y as usize][*x as usize] = match item.label {
+                    '[' if *x == left => '[',
+                    '[' if *x == right => ']',
+                    '[' => '=',
+                    label => label,
+                };
             }
-            _ => {},
         }
+        display[self.robot_y as usize][self.robot_x as usize] = '@';
+        write!(f, "{}", display.iter().map(|row| row.iter().collect::<String>()).collect::<Vec<String>>().join("\n"))
+    }
+}
 
+fn simulate(input: &str, wide: bool) -> Warehouse {
+    let (map_text, moves) = split_input(input);
+    let mut warehouse = Warehouse::parse(&map_text, wide);
+
+    for instruction in moves.chars() {
+        warehouse.move_robot(instruction);
     }
-    success
+
+    warehouse
+}
+
+fn part1(input: &String) -> String {
+    format!("{}", simulate(input, false).get_coordinate_sum())
 }
 
 fn part2(input: &String) -> String {
-    let mut map: Vec<Vec<Entity>> = Vec::new();
-    let mut robot_x: i32 = 0;
-    let mut robot_y: i32 = 0;
+    format!("{}", simulate(input, true).get_coordinate_sum())
+}
 
-    let mut row_index = 0;
-    let mut lines = input.lines();
-    loop {
-        let mut col_index = 0;
-        let line = lines.next().unwrap();
-        if line.is_empty() {
-            break;
-        }
-        let mut row: Vec<Entity> = Vec::new();
-        for entity in line.chars() {
-            match entity {
-                '#' => {
-                    row.push(Entity::Wall);
-                    row.push(Entity::Wall);
-                },
-                'O' => {
-                    row.push(Entity::BoxLeft);
-                    row.push(Entity::BoxRight);
-                },
-                '@' => {
-                    robot_x = col_index * 2;
-                    robot_y = row_index;
-                    row.push(Entity::Space);
-                    row.push(Entity::Space);
-                },
-                _ => {
-                    row.push(Entity::Space);
-                    row.push(Entity::Space);
-                },
-            }
-            col_index += 1;
-        }
-        map.push(row);
+#[derive(PartialEq, Debug)]
+enum Command {
+    Move(char),
+    Undo,
+    Redo,
+    Save(String),
+    Quit
+}
 
-        row_index += 1;
+// Moves can be typed as <^>v or arrow keys, which arrive as escape sequences once the line is entered.
+fn parse_command(line: &str) -> Vec<Command> {
+    let line = line.trim();
+    match line {
+        "u" | "undo" => return vec![Command::Undo],
+        "r" | "redo" => return vec![Command::Redo],
+        "q" | "quit" => return vec![Command::Quit],
+        _ => {}
     }
-    let size = map.len();
-
-    for instruction in lines.collect::<String>().chars() {
-        let mut direction_x: i32 = 0;
-        let mut direction_y: i32 = 0;
-        match instruction {
-            '<' => { direction_x = -1; }
-            '>' => { direction_x = 1; }
-            '^' => { direction_y = -1; }
-            'v' => { direction_y = 1; }
-            _ => {}
-        }
-        if direction_y == 0 {
-            if push(&mut map, size as i32 * 2, robot_x, robot_y, direction_x, direction_y, Entity::Space) {
-                robot_x += direction_x;
-            }
-        } else {
-            if push_wide(&mut map, robot_x, robot_y, direction_y, Entity::Space, true) {
-                push_wide(&mut map, robot_x, robot_y, direction_y, Entity::Space, false);
-                robot_y += direction_y;
+    if let Some(path) = line.strip_prefix("save ") {
+        return vec![Command::Save(path.trim().to_string())]
+    }
+
+    let line = line.replace("\x1b[A", "^").replace("\x1b[B", "v").replace("\x1b[C", ">").replace("\x1b[D", "<");
+    line.chars().filter(|instruction| "<^>v".contains(*instruction)).map(Command::Move).collect()
+}
+
+struct Session {
+    map_text: String,
+    warehouse: Warehouse,
+    moves: Vec<char>,
+    undo: Vec<Warehouse>,
+    redo: Vec<(Warehouse, char)>
+}
+
+impl Session {
+    fn new(map_text: &str, wide: bool) -> Session {
+        Session { map_text: map_text.to_string(), warehouse: Warehouse::parse(map_text, wide), moves: Vec::new(), undo: Vec::new(), redo: Vec::new() }
+    }
+
+    fn apply(&mut self, instruction: char) {
+        self.undo.push(self.warehouse.clone());
+        self.warehouse.move_robot(instruction);
+        self.moves.push(instruction);
+        self.redo.clear();
+    }
+
+    fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            None => false,
+            Some(previous) => {
+                let instruction = self.moves.pop().unwrap();
+                self.redo.push((std::mem::replace(&mut self.warehouse, previous), instruction));
+                true
             }
         }
     }
 
-    let mut coordinate_sum = 0;
-    for row in 0..size {
-        for col in 0..size * 2 {
-            if map[row][col] == Entity::BoxLeft {
-                // let height = usize::min(row, size - row - 1);
-                let height = row;
-                let width = col;
-                coordinate_sum += height * 100 + width;
+    fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            None => false,
+            Some((next, instruction)) => {
+                self.undo.push(std::mem::replace(&mut self.warehouse, next));
+                self.moves.push(instruction);
+                true
             }
         }
     }
 
-    format!("{}", coordinate_sum)
+    // The starting map and the recorded moves, laid out like the puzzle input.
+    fn save(&self) -> String {
+        let moves: Vec<String> = self.moves.chunks(70).map(|chunk| chunk.iter().collect()).collect();
+        format!("{}\n\n{}\n", self.map_text, moves.join("\n"))
+    }
+}
+
+fn draw_session(session: &Session, output: &mut impl Write) {
+    writeln!(output, "{}\nGPS: {}", session.warehouse, session.warehouse.get_coordinate_sum()).unwrap();
+}
+
+// Redraws the warehouse after every move, undo and redo, so a line of several moves shows each step.
+fn run_session(session: &mut Session, input: impl BufRead, output: &mut impl Write) {
+    writeln!(output, "{}", session.warehouse).unwrap();
+    for line in input.lines() {
+        for command in parse_command(&line.unwrap()) {
+            match command {
+                Command::Move(instruction) => {
+                    session.apply(instruction);
+                    draw_session(session, output);
+                },
+                Command::Undo => if session.undo() { draw_session(session, output) } else { writeln!(output, "Nothing to undo").unwrap() },
+                Command::Redo => if session.redo() { draw_session(session, output) } else { writeln!(output, "Nothing to redo").unwrap() },
+                Command::Save(path) => match std::fs::write(&path, session.save()) {
+                    Ok(()) => writeln!(output, "Saved {} moves to {}", session.moves.len(), path).unwrap(),
+                    Err(error) => writeln!(output, "Unable to save to {}: {}", path, error).unwrap(),
+                },
+                Command::Quit => return,
+            }
+        }
+    }
 }
 
 fn main() {
@@ -218,13 +318,30 @@ fn main() {
     let input1 = &std::fs::read_to_string("day15.txt").expect("Unable to read input file");
     println!("Part1: {}", part1(input1));
     println!("Part2: {}", part2(input1));
+
+    if std::env::args().any(|arg| arg == "--render") {
+        let warehouse = simulate(input1, std::env::args().any(|arg| arg == "--wide"));
+        println!("{}", warehouse);
+        for ((x, y), coordinate) in warehouse.get_coordinates() {
+            println!("{},{}: {}", x, y, coordinate);
+        }
+        let args: Vec<String> = std::env::args().collect();
+        if let Some(path) = args.iter().position(|arg| arg == "--expect").and_then(|index| args.get(index + 1)) {
+            let expected = std::fs::read_to_string(path).expect("Unable to read expected map");
+            println!("Matches {}: {}", path, warehouse.matches(&expected));
+        }
+    }
+
+    if std::env::args().any(|arg| arg == "--interactive") {
+        let mut session = Session::new(&split_input(input1).0, std::env::args().any(|arg| arg == "--wide"));
+        run_session(&mut session, std::io::stdin().lock(), &mut std::io::stdout());
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::part1;
-    use crate::part2;
+    use crate::{parse_command, part1, part2, run_session, simulate, split_input, Command, Session, Warehouse};
 
     #[test]
     fn test_part1_small() {
@@ -282,8 +399,41 @@ v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^"#;
 #######
 
 <vv<<^^<<^^"#;
+        let expected1 = "618".to_string();
+
+        let mut warehouse = Warehouse::parse(example1, true);
+        assert!(warehouse.matches(r#"
+            ##############
+            ##......##..##
+            ##..........##
+            ##....[][]@.##
+            ##....[]....##
+            ##..........##
+            ##############"#));
+        for instruction in "<vv<<^".chars() {
+            warehouse.move_robot(instruction);
+        }
+        assert!(warehouse.matches(r#"
+            ##############
+            ##......##..##
+            ##...[][]...##
+            ##....[]....##
+            ##.....@....##
+            ##..........##
+            ##############"#));
+        assert!(!warehouse.move_robot('^'));
 
-        part2(&example1.to_string());
+        let warehouse = simulate(example1, true);
+        assert!(warehouse.matches(r#"
+            ##############
+            ##...[].##..##
+            ##...@.[]...##
+            ##....[]....##
+            ##..........##
+            ##..........##
+            ##############"#));
+        assert_eq!(warehouse.get_coordinates(), vec![((5, 1), 105), ((7, 2), 207), ((6, 3), 306)]);
+        assert_eq!(part2(&example1.to_string()), expected1);
     }
 
     #[test]
@@ -313,4 +463,90 @@ v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^"#;
 
         assert_eq!(part2(&example1.to_string()), expected1);
     }
+
+    #[test]
+    fn test_session() {
+        let example1 = r#"########
+#..O.O.#
+##@.O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########"#;
+
+        assert_eq!(parse_command("<^\x1b[B\x1b[C x"), vec![Command::Move('<'), Command::Move('^'), Command::Move('v'), Command::Move('>')]);
+        assert_eq!(parse_command("save moves.txt"), vec![Command::Save("moves.txt".to_string())]);
+
+        let mut session = Session::new(example1, false);
+        let mut output: Vec<u8> = Vec::new();
+        run_session(&mut session, "<^^>>>vv\nu\nu\nr\n<v>>v<<\nq\n>>>>".as_bytes(), &mut output);
+
+        let mut replayed = Warehouse::parse(example1, false);
+        for instruction in "<^^>>>v<v>>v<<".chars() {
+            replayed.move_robot(instruction);
+        }
+        assert_eq!(session.warehouse, replayed);
+        assert_eq!(session.save(), format!("{}\n\n<^^>>>v<v>>v<<\n", example1));
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().last().unwrap(), "GPS: 2028");
+        assert_eq!(output.lines().filter(|line| line.starts_with("GPS: ")).count(), 18);
+
+        while session.undo() {}
+        assert_eq!(session.warehouse, Warehouse::parse(example1, false));
+        assert!(session.redo());
+        assert_eq!(session.moves, vec!['<']);
+        assert_eq!(split_input(&session.save()).0, example1);
+
+        // A bad path is reported and the session carries on
+        let mut output: Vec<u8> = Vec::new();
+        run_session(&mut session, "save /nonexistent/moves.txt
+>
+".as_bytes(), &mut output);
+        assert!(String::from_utf8(output).unwrap().contains("Unable to save to /nonexistent/moves.txt"));
+        assert_eq!(session.moves, vec!['<', '>']);
+    }
+
+    #[test]
+    fn test_shaped_boxes() {
+        let example1 = r#"##########
+#@O[=]aa.#
+##########"#;
+
+        let mut warehouse = Warehouse::parse(example1, false);
+        assert!(warehouse.move_robot('>'));
+        assert_eq!(warehouse.to_string(), "##########\n#.@O[=]aa#\n##########");
+        assert!(!warehouse.move_robot('>'));
+        assert_eq!(warehouse.get_coordinate_sum(), 103 + 104 + 107);
+
+        let example2 = r#"#######
+#.....#
+#.....#
+#.bb..#
+#..b..#
+#.[=].#
+#..@..#
+#######"#;
+        let expected2 = r#"#######
+#.bb..#
+#..b..#
+#.[=].#
+#..@..#
+#.....#
+#.....#
+#######"#;
+
+        let mut warehouse = Warehouse::parse(example2, false);
+        assert!(warehouse.move_robot('^'));
+        assert!(warehouse.move_robot('^'));
+        assert!(!warehouse.move_robot('^'));
+        assert_eq!(warehouse.to_string(), expected2);
+        for instruction in "<<^>".chars() {
+            assert!(warehouse.move_robot(instruction));
+        }
+        assert!(!warehouse.move_robot('>'));
+        assert!(warehouse.move_robot('^'));
+        assert!(warehouse.move_robot('>'));
+        assert_eq!(warehouse.to_string(), "#######\n#..bb.#\n#..@b.#\n#..[=]#\n#.....#\n#.....#\n#.....#\n#######");
+    }
 }
@@ -1,294 +1,466 @@
 use std::fs;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::collections::hash_map::Entry;
-use std::collections::VecDeque;
-
-struct Position {
-    row: usize,
-    col: usize
+use std::collections::BinaryHeap;
+use std::cmp::Reverse;
+use std::fmt;
+
+// Path counts grow exponentially with the number of equal alternatives, so they're kept as base 10^9 digits.
+#[derive(Clone, Debug, PartialEq)]
+struct BigCount {
+    digits: Vec<u64>
 }
 
-struct CostDirection {
-    cost: i32,
-    row: i32,
-    col: i32,
-}
+const DIGIT_BASE: u64 = 1_000_000_000;
 
-struct PathNode {
-    position: Position,
-    inputs: Vec<CostDirection>,
-    outputs: Vec<CostDirection>
-}
+impl BigCount {
+    fn from(value: u64) -> BigCount {
+        let mut digits = Vec::new();
+        let mut value = value;
+        while value > 0 {
+            digits.push(value % DIGIT_BASE);
+            value /= DIGIT_BASE;
+        }
+        BigCount { digits }
+    }
 
-struct NodeVector {
-    node_index: usize,
-    row_delta: i32,
-    col_delta: i32
-}
+    fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
 
-impl PathNode {
-    fn new(row: usize, col: usize, row_delta: i32, col_delta: i32, cost: i32) -> PathNode {
-        let mut node = PathNode { position: Position { row, col }, inputs: Vec::new(), outputs: Vec::new() };
-        node.inputs.push(CostDirection { cost, row: row_delta, col: col_delta });
-        node
+    fn add(&self, other: &BigCount) -> BigCount {
+        let mut digits = Vec::new();
+        let mut carry = 0;
+        for index in 0..self.digits.len().max(other.digits.len()) {
+            let sum = self.digits.get(index).unwrap_or(&0) + other.digits.get(index).unwrap_or(&0) + carry;
+            digits.push(sum % DIGIT_BASE);
+            carry = sum / DIGIT_BASE;
+        }
+        if carry > 0 {
+            digits.push(carry);
+        }
+        BigCount { digits }
     }
 
-    fn add_input(&mut self, row_delta: i32, col_delta: i32, cost: i32) {
-        for input in &mut self.inputs {
-            if input.row == row_delta && input.col == col_delta {
-                input.cost = cost;
-                return
+    fn multiply(&self, other: &BigCount) -> BigCount {
+        if self.is_zero() || other.is_zero() {
+            return BigCount::from(0)
+        }
+        let mut digits = vec![0; self.digits.len() + other.digits.len()];
+        for (first_index, first) in self.digits.iter().enumerate() {
+            let mut carry = 0;
+            for (second_index, second) in other.digits.iter().enumerate() {
+                let product = digits[first_index + second_index] + first * second + carry;
+                digits[first_index + second_index] = product % DIGIT_BASE;
+                carry = product / DIGIT_BASE;
             }
+            digits[first_index + other.digits.len()] += carry;
         }
-        self.inputs.push(CostDirection { cost, row: row_delta, col: col_delta })
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        BigCount { digits }
     }
 }
 
-struct Graph {
-    nodes: HashMap<usize, PathNode>
+impl fmt::Display for BigCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.digits.split_last() {
+            None => write!(f, "0"),
+            Some((last, rest)) => {
+                write!(f, "{}", last)?;
+                rest.iter().rev().try_for_each(|digit| write!(f, "{:09}", digit))
+            }
+        }
+    }
 }
 
-impl Graph {
-    fn add(&mut self, index: usize, node: PathNode) {
-        self.nodes.insert(index, node);
-    }
+// East, south, west, north, so turning clockwise adds one.
+const DIRECTIONS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
 
-    fn add_input(&mut self, row: usize, col: usize, row_delta: i32, col_delta: i32, cost: i32) {
-        let node_index = row * 1000 + col;
-        match self.nodes.entry(node_index) {
-            Entry::Occupied(n) => {
-                let node = n.into_mut();
-                node.add_input(row_delta, col_delta, cost);
-            }
-            Entry::Vacant(v) => {
-                let node = PathNode::new(row, col, row_delta, col_delta, cost);
-                v.insert(node);
-            }
-        }
+struct Costs {
+    step: i64,
+    turn: i64,
+    reverse: i64
+}
+
+impl Costs {
+    fn standard() -> Costs {
+        Costs { step: 1, turn: 1000, reverse: 2000 }
     }
+}
 
-    fn add_output(&mut self, node_index: usize, row_delta: i32, col_delta: i32) -> Option<i32> {
-        let node = &mut self.nodes.get_mut(&node_index).unwrap();
-        for output in &mut node.outputs {
-            if output.row == row_delta && output.col == col_delta {
-                let mut updated = false;
-                for input in &node.inputs {
-                    if input.row == row_delta && input.col == col_delta && output.cost > input.cost + 1 {
-                        updated = true;
-                        output.cost = input.cost + 1;
-                    } else if output.cost > input.cost + 1001 {
-                        updated = true;
-                        output.cost = input.cost + 1001;
-                    }
+struct Maze {
+    walls: Vec<Vec<bool>>,
+    start: (usize, usize),
+    end: (usize, usize)
+}
+
+struct Route {
+    score: i64,
+    tiles: Vec<(usize, usize)>,
+    turns: usize,
+    // Tiles shared with the optimal route where this one heads off somewhere else.
+    divergence: Vec<(usize, usize)>
+}
+
+struct OptimalPaths {
+    score: i64,
+    count: BigCount,
+    tiles: Vec<(usize, usize)>,
+    tile_counts: HashMap<(usize, usize), BigCount>
+}
+
+impl Maze {
+    fn parse(input: &str) -> Maze {
+        let mut walls: Vec<Vec<bool>> = Vec::new();
+        let mut start = (0, 0);
+        let mut end = (0, 0);
+
+        for (row_index, line) in input.lines().filter(|line| !line.is_empty()).enumerate() {
+            for (col_index, entity) in line.chars().enumerate() {
+                match entity {
+                    'S' => start = (row_index, col_index),
+                    'E' => end = (row_index, col_index),
+                    _ => {},
                 }
-                return if updated { Some(output.cost) } else { None }
             }
+            walls.push(line.chars().map(|entity| entity == '#').collect());
         }
 
-        let mut cost = i32::MAX;
-        for input in &node.inputs {
-            if input.row == row_delta && input.col == col_delta && cost > input.cost + 1 {
-                cost = input.cost + 1;
-            } else if cost > input.cost + 1001 {
-                cost = input.cost + 1001;
-            }
-        }
-        node.outputs.push(CostDirection { cost, row: row_delta, col: col_delta });
+        Maze { walls, start, end }
+    }
 
-        Some(cost)
+    // States are a tile and a facing, packed as (row * width + col) * 4 + facing.
+    fn get_state(&self, (row, col): (usize, usize), facing: usize) -> usize {
+        (row * self.get_width() + col) * 4 + facing
     }
 
-    fn get_inputs_for_output(&self, node_index: usize, row_delta: i32, col_delta: i32) -> Vec<NodeVector> {
-        let mut output_cost = 0;
-        for output in &self.nodes[&node_index].outputs {
-            if output.row == row_delta && output.col == col_delta {
-                output_cost = output.cost;
-            }
+    fn get_tile(&self, state: usize) -> (usize, usize) {
+        (state / 4 / self.get_width(), state / 4 % self.get_width())
+    }
+
+    fn get_width(&self) -> usize {
+        self.walls.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
+
+    fn is_open(&self, row: i32, col: i32) -> bool {
+        row >= 0 && col >= 0 && self.walls.get(row as usize).and_then(|walls| walls.get(col as usize)) == Some(&false)
+    }
+
+    // Moving forward, rotating a quarter either way, or rotating all the way round.
+    fn get_moves(&self, state: usize, costs: &Costs) -> Vec<(usize, i64)> {
+        let (row, col) = self.get_tile(state);
+        let facing = state % 4;
+        let mut moves = vec![
+            (self.get_state((row, col), (facing + 1) % 4), costs.turn),
+            (self.get_state((row, col), (facing + 3) % 4), costs.turn),
+            (self.get_state((row, col), (facing + 2) % 4), costs.reverse),
+        ];
+        let (row_delta, col_delta) = DIRECTIONS[facing];
+        if self.is_open(row as i32 + row_delta, col as i32 + col_delta) {
+            let next = ((row as i32 + row_delta) as usize, (col as i32 + col_delta) as usize);
+            moves.push((self.get_state(next, facing), costs.step));
         }
-        let mut min_inputs: Vec<NodeVector> = Vec::new();
-        for input in &self.nodes[&node_index].inputs {
-            let is_valid_straight = input.row == row_delta && input.col == col_delta && input.cost + 1 == output_cost;
-            let is_valid_turn = input.row != row_delta && input.col != col_delta && input.cost + 1001 == output_cost;
-            if is_valid_straight || is_valid_turn {
-                let next_node_index = (node_index as i32 / 1000 - input.row) * 1000 + node_index as i32 % 1000 - input.col;
-                min_inputs.push(NodeVector { node_index: next_node_index as usize, row_delta: input.row, col_delta: input.col });
+        moves
+    }
+
+    // Cheapest score to reach every state from the start facing east, with the state each was reached from.
+    fn get_scores(&self, costs: &Costs, moves: fn(&Maze, usize, &Costs) -> Vec<(usize, i64)>) -> (Vec<i64>, Vec<Option<usize>>) {
+        let state_count = self.walls.len() * self.get_width() * 4;
+        let mut scores = vec![i64::MAX; state_count];
+        let mut previous: Vec<Option<usize>> = vec![None; state_count];
+        let mut queue = BinaryHeap::new();
+
+        let start = self.get_state(self.start, 0);
+        scores[start] = 0;
+        queue.push(Reverse((0, start)));
+
+        while let Some(Reverse((score, state))) = queue.pop() {
+            if score > scores[state] {
+                continue;
+            }
+            for (next, cost) in moves(self, state, costs) {
+                if score + cost < scores[next] {
+                    scores[next] = score + cost;
+                    previous[next] = Some(state);
+                    queue.push(Reverse((score + cost, next)));
+                }
             }
         }
 
-        min_inputs
+        (scores, previous)
     }
 
-    fn get_min_cost(&self, node_index: &usize) -> i32 {
-        let mut cost = i32::MAX;
-        for input in &self.nodes[node_index].inputs {
-            if input.cost < cost {
-                cost = input.cost;
-            }
+    fn find_route(&self, costs: &Costs) -> Option<Route> {
+        let (scores, previous) = self.get_scores(costs, Maze::get_moves);
+        let end = (0..4).map(|facing| self.get_state(self.end, facing)).min_by_key(|state| scores[*state])?;
+        if scores[end] == i64::MAX {
+            return None
+        }
+
+        let mut states = vec![end];
+        while let Some(before) = previous[*states.last().unwrap()] {
+            states.push(before);
         }
-        cost
+        states.reverse();
+
+        Some(self.get_route(&states, scores[end], &HashSet::new()))
     }
 
-    fn get_end_vector(&self, node_index: usize) -> NodeVector {
-        let mut candidate: Option<&CostDirection> = None;
-        for input in &self.nodes[&node_index].inputs {
-            if candidate.is_none() || input.cost < candidate.unwrap().cost {
-                candidate = Some(input);
+    fn get_route(&self, states: &[usize], score: i64, optimum: &HashSet<(usize, usize)>) -> Route {
+        let mut tiles: Vec<(usize, usize)> = Vec::new();
+        let mut turns = 0;
+        for (index, state) in states.iter().enumerate() {
+            if tiles.last() != Some(&self.get_tile(*state)) {
+                tiles.push(self.get_tile(*state));
+            }
+            if index > 0 {
+                turns += match (state % 4 + 4 - states[index - 1] % 4) % 4 {
+                    0 => 0,
+                    2 => 2,
+                    _ => 1,
+                };
             }
         }
 
-        let input_with_min_cost = candidate.unwrap();
-        let next_node_index = (node_index as i32 / 1000 - input_with_min_cost.row) * 1000 + node_index as i32 % 1000 - input_with_min_cost.col;
-        NodeVector { node_index: next_node_index as usize, row_delta: input_with_min_cost.row, col_delta: input_with_min_cost.col }
+        let divergence = tiles.windows(2)
+            .filter(|pair| optimum.contains(&pair[0]) && !optimum.contains(&pair[1]))
+            .map(|pair| pair[0])
+            .collect();
+
+        Route { score, tiles, turns, divergence }
     }
 
-    fn get_position(&self, node_index: &usize) -> Position {
-        let position = &self.nodes[node_index].position;
-        Position { row: position.row, col: position.col }
+    // Turning and then stepping off the tile as a single move, so a path is fixed by the tiles it crosses.
+    fn get_steps(&self, state: usize, costs: &Costs) -> Vec<(usize, i64)> {
+        let (row, col) = self.get_tile(state);
+        let mut steps = Vec::new();
+        for (facing, (row_delta, col_delta)) in DIRECTIONS.iter().enumerate() {
+            if !self.is_open(row as i32 + row_delta, col as i32 + col_delta) {
+                continue;
+            }
+            let rotation = match (facing + 4 - state % 4) % 4 {
+                0 => 0,
+                2 => costs.reverse.min(costs.turn * 2),
+                _ => costs.turn,
+            };
+            let next = ((row as i32 + row_delta) as usize, (col as i32 + col_delta) as usize);
+            steps.push((self.get_state(next, facing), rotation + costs.step));
+        }
+        steps
     }
-}
 
-fn part1(input: &String) -> String {
-    let size = input.lines().count();
-    let mut walls = vec!(vec!(false; size); size);
-
-    let mut start_x = 0;
-    let mut start_y = 0;
-    let mut end_x = 0;
-    let mut end_y = 0;
-
-    let mut row_index = 0;
-    for line in input.lines() {
-        let mut col_index = 0;
-        for entity in line.chars() {
-            match entity {
-                '#' => { walls[row_index][col_index] = true; },
-                'S' => {
-                    start_x = col_index;
-                    start_y = row_index;
-                },
-                'E' => {
-                    end_x = col_index;
-                    end_y = row_index;
-                },
-                _ => {},
+    // Cheapest way on from a state to the end, without passing through the given states or taking the given moves.
+    fn find_spur(&self, from: usize, costs: &Costs, removed_states: &HashSet<usize>, removed_moves: &HashSet<(usize, usize)>) -> Option<(i64, Vec<usize>)> {
+        let state_count = self.walls.len() * self.get_width() * 4;
+        let mut scores = vec![i64::MAX; state_count];
+        let mut previous: Vec<Option<usize>> = vec![None; state_count];
+        let mut queue = BinaryHeap::new();
+        scores[from] = 0;
+        queue.push(Reverse((0, from)));
+
+        while let Some(Reverse((score, state))) = queue.pop() {
+            if score > scores[state] {
+                continue;
+            }
+            if self.get_tile(state) == self.end {
+                let mut states = vec![state];
+                while let Some(before) = previous[*states.last().unwrap()] {
+                    states.push(before);
+                }
+                states.reverse();
+                return Some((score, states))
+            }
+            for (next, cost) in self.get_steps(state, costs) {
+                if removed_states.contains(&next) || removed_moves.contains(&(state, next)) {
+                    continue;
+                }
+                if score + cost < scores[next] {
+                    scores[next] = score + cost;
+                    previous[next] = Some(state);
+                    queue.push(Reverse((score + cost, next)));
+                }
             }
-            col_index += 1;
         }
 
-        row_index += 1;
+        None
     }
 
-    let mut nodes = Graph { nodes: HashMap::new() };
-    let mut alternatives = VecDeque::new();
-    let start_node = PathNode::new(start_y, start_x, 0, 1, 0);
-    let start_node_index = start_y * 1000 + start_x;
-    alternatives.push_back(start_node_index);
-    nodes.add(start_node_index, start_node);
+    // Yen's k shortest paths over the (tile, facing) states.
+    fn find_routes(&self, costs: &Costs, limit: usize, max_extra: Option<i64>) -> Vec<Route> {
+        let start = self.get_state(self.start, 0);
+        let mut routes: Vec<Route> = Vec::new();
+        let mut paths: Vec<(i64, Vec<usize>)> = Vec::new();
+        let mut candidates = BinaryHeap::new();
+        let mut seen: HashSet<Vec<usize>> = HashSet::new();
+        let mut optimum: HashSet<(usize, usize)> = HashSet::new();
+        if limit == 0 {
+            return routes
+        }
 
-    loop {
-        match alternatives.pop_front() {
-            None => {
+        if let Some(path) = self.find_spur(start, costs, &HashSet::new(), &HashSet::new()) {
+            seen.insert(path.1.clone());
+            candidates.push(Reverse(path));
+        }
+
+        while let Some(Reverse((score, path))) = candidates.pop() {
+            if routes.first().is_some_and(|best| max_extra.is_some_and(|max_extra| score > best.score + max_extra)) {
                 break;
             }
-            Some(node_index) => {
-                move_along_direction(&walls, &mut nodes, &mut alternatives, node_index, 0, -1);
-                move_along_direction(&walls, &mut nodes, &mut alternatives, node_index, 0, 1);
-                move_along_direction(&walls, &mut nodes, &mut alternatives, node_index, -1, 0);
-                move_along_direction(&walls, &mut nodes, &mut alternatives, node_index, 1, 0);
+            let route = self.get_route(&path, score, &optimum);
+            if routes.is_empty() {
+                optimum = route.tiles.iter().copied().collect();
+            }
+            routes.push(route);
+            if routes.len() == limit {
+                break;
             }
-        }
-    }
 
-    format!("{}", nodes.get_min_cost(&(end_y * 1000 + end_x)))
-}
+            let mut root_score = 0;
+            for index in 0..path.len() - 1 {
+                let root = &path[..=index];
+                let removed_states: HashSet<usize> = root[..index].iter().copied().collect();
+                let removed_moves: HashSet<(usize, usize)> = paths.iter().map(|(_, known)| known)
+                    .chain([&path])
+                    .filter(|known| known.len() > index + 1 && known[..=index] == *root)
+                    .map(|known| (known[index], known[index + 1]))
+                    .collect();
+
+                if let Some((spur_score, spur)) = self.find_spur(path[index], costs, &removed_states, &removed_moves) {
+                    let candidate: Vec<usize> = root[..index].iter().chain(spur.iter()).copied().collect();
+                    if seen.insert(candidate.clone()) {
+                        candidates.push(Reverse((root_score + spur_score, candidate)));
+                    }
+                }
 
-fn move_along_direction(walls: &Vec<Vec<bool>>, nodes: &mut Graph, alternatives: &mut VecDeque<usize>, node_index: usize, direction_y: i32, direction_x: i32) {
-    if node_index == 1000 + walls.len() - 2 {
-        return
-    }
-    let position = &nodes.get_position(&node_index);
-    let target_row: usize = (position.row as i32 + direction_y) as usize;
-    let target_col: usize = (position.col as i32 + direction_x) as usize;
-    if !walls[target_row][target_col] {
-        let cost = nodes.add_output(node_index, direction_y, direction_x);
-        if cost.is_some() {
-            alternatives.push_back(target_row * 1000 + target_col);
-            nodes.add_input(target_row, target_col, direction_y, direction_x, cost.unwrap());
+                root_score += self.get_steps(path[index], costs).iter()
+                    .find(|(next, _)| *next == path[index + 1])
+                    .unwrap().1;
+            }
+            paths.push((score, path));
         }
+
+        routes
     }
-}
 
-fn part2(input: &String) -> String {
-    let size = input.lines().count();
-    let mut walls = vec!(vec!(false; size); size);
-
-    let mut start_x = 0;
-    let mut start_y = 0;
-    let mut end_x = 0;
-    let mut end_y = 0;
-
-    let mut row_index = 0;
-    for line in input.lines() {
-        let mut col_index = 0;
-        for entity in line.chars() {
-            match entity {
-                '#' => { walls[row_index][col_index] = true; },
-                'S' => {
-                    start_x = col_index;
-                    start_y = row_index;
-                },
-                'E' => {
-                    end_x = col_index;
-                    end_y = row_index;
-                },
-                _ => {},
+    // Counts work on the graph of steps that keep a state at its cheapest score. Turns are folded into the steps so
+    // that each path is counted once, however the reindeer rotates. Moves that cost nothing can loop in that graph,
+    // and then there are endlessly many optimal paths.
+    fn get_optimal_paths(&self, costs: &Costs) -> Result<OptimalPaths, String> {
+        if costs.step < 0 || costs.turn < 0 || costs.reverse < 0 {
+            return Err("Costs can't be negative".to_string())
+        }
+
+        let (scores, _) = self.get_scores(costs, Maze::get_steps);
+        let score = (0..4).map(|facing| scores[self.get_state(self.end, facing)]).min()
+            .filter(|score| *score != i64::MAX)
+            .ok_or("No route from start to end")?;
+
+        // Paths stop as soon as they reach the end tile.
+        let get_next = |state: usize| -> Vec<usize> {
+            if self.get_tile(state) == self.end {
+                return Vec::new()
+            }
+            self.get_steps(state, costs).into_iter()
+                .filter(|(next, cost)| scores[*next] <= score && scores[state] + cost == scores[*next])
+                .map(|(next, _)| next)
+                .collect()
+        };
+
+        // Only states that can still go on to the end optimally, in an order where every step goes forwards.
+        let candidates: Vec<usize> = (0..scores.len()).filter(|state| scores[*state] <= score).collect();
+        let mut before: HashMap<usize, Vec<usize>> = HashMap::new();
+        for state in candidates.iter() {
+            for next in get_next(*state) {
+                before.entry(next).or_default().push(*state);
+            }
+        }
+        let mut useful: HashSet<usize> = (0..4).map(|facing| self.get_state(self.end, facing)).filter(|state| scores[*state] == score).collect();
+        let mut pending: Vec<usize> = useful.iter().copied().collect();
+        while let Some(state) = pending.pop() {
+            for previous in before.get(&state).unwrap_or(&Vec::new()) {
+                if useful.insert(*previous) {
+                    pending.push(*previous);
+                }
             }
-            col_index += 1;
         }
 
-        row_index += 1;
-    }
+        let mut incoming: HashMap<usize, usize> = useful.iter().map(|state| (*state, 0)).collect();
+        for state in useful.iter() {
+            for next in get_next(*state).into_iter().filter(|next| useful.contains(next)) {
+                *incoming.get_mut(&next).unwrap() += 1;
+            }
+        }
+        let mut states: Vec<usize> = useful.iter().copied().filter(|state| incoming[state] == 0).collect();
+        let mut index = 0;
+        while index < states.len() {
+            for next in get_next(states[index]).into_iter().filter(|next| useful.contains(next)) {
+                let count = incoming.get_mut(&next).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    states.push(next);
+                }
+            }
+            index += 1;
+        }
+        if states.len() < useful.len() {
+            return Err("Moves that cost nothing make a loop, so there are endlessly many optimal paths".to_string())
+        }
 
-    let mut nodes = Graph { nodes: HashMap::new() };
-    let mut alternatives = VecDeque::new();
-    let start_node = PathNode::new(start_y, start_x, 0, 1, 0);
-    let start_node_index = start_y * 1000 + start_x;
-    alternatives.push_back(start_node_index);
-    nodes.add(start_node_index, start_node);
+        let zero = BigCount::from(0);
+        let start = self.get_state(self.start, 0);
+        let mut arrivals: Vec<BigCount> = vec![zero.clone(); scores.len()];
+        arrivals[start] = BigCount::from(1);
+        for state in states.iter() {
+            for next in get_next(*state).into_iter().filter(|next| useful.contains(next)) {
+                arrivals[next] = arrivals[next].add(&arrivals[*state]);
+            }
+        }
 
-    loop {
-        match alternatives.pop_front() {
-            None => {
-                break;
+        let mut departures: Vec<BigCount> = vec![zero.clone(); scores.len()];
+        for state in states.iter().rev() {
+            let mut count = if self.get_tile(*state) == self.end { BigCount::from(1) } else { zero.clone() };
+            for next in get_next(*state).into_iter().filter(|next| useful.contains(next)) {
+                count = count.add(&departures[next]);
             }
-            Some(node_index) => {
-                move_along_direction(&walls, &mut nodes, &mut alternatives, node_index, 0, -1);
-                move_along_direction(&walls, &mut nodes, &mut alternatives, node_index, 0, 1);
-                move_along_direction(&walls, &mut nodes, &mut alternatives, node_index, -1, 0);
-                move_along_direction(&walls, &mut nodes, &mut alternatives, node_index, 1, 0);
+            departures[*state] = count;
+        }
+
+        let mut tile_counts: HashMap<(usize, usize), BigCount> = HashMap::new();
+        for state in states.iter() {
+            let through = arrivals[*state].multiply(&departures[*state]);
+            if !through.is_zero() {
+                let tile = tile_counts.entry(self.get_tile(*state)).or_insert(zero.clone());
+                *tile = tile.add(&through);
             }
         }
+        let mut tiles: Vec<(usize, usize)> = tile_counts.keys().copied().collect();
+        tiles.sort();
+
+        Ok(OptimalPaths { score, count: departures[start].clone(), tiles, tile_counts })
     }
 
-    let mut hot_nodes = HashSet::new();
-    let end_index = end_y * 1000 + end_x;
-    hot_nodes.insert(end_index);
+    fn render(&self, tiles: &[(usize, usize)]) -> String {
+        let mut display: Vec<Vec<char>> = self.walls.iter()
+            .map(|row| row.iter().map(|wall| if *wall { '#' } else { '.' }).collect())
+            .collect();
+        for (row, col) in tiles {
+            display[*row][*col] = 'O';
+        }
+        display.iter().map(|row| row.iter().collect::<String>()).collect::<Vec<String>>().join("\n")
+    }
+}
 
-    let end_vector = nodes.get_end_vector(end_index);
-    hot_nodes.insert(end_vector.node_index);
-    work_backwards(&nodes, &mut hot_nodes, end_vector.node_index, end_vector.row_delta, end_vector.col_delta);
 
-    format!("{}", hot_nodes.len())
+fn part1(input: &String) -> String {
+    let route = Maze::parse(input).find_route(&Costs::standard()).expect("No route from start to end");
+    format!("{}", route.score)
 }
 
-fn work_backwards(nodes: &Graph, hot_nodes: &mut HashSet<usize>, node_index: usize, row_delta: i32, col_delta: i32) {
-    for next_node in nodes.get_inputs_for_output(node_index, row_delta, col_delta) {
-        if !hot_nodes.contains(&next_node.node_index) && next_node.node_index % 1000 != 0 {
-            hot_nodes.insert(next_node.node_index);
-            work_backwards(nodes, hot_nodes, next_node.node_index, next_node.row_delta, next_node.col_delta);
-        }
-    }
+fn part2(input: &String) -> String {
+    let paths = Maze::parse(input).get_optimal_paths(&Costs::standard()).unwrap();
+    format!("{}", paths.tiles.len())
 }
 
 fn main() {
@@ -296,14 +468,33 @@ fn main() {
     let input1 = &fs::read_to_string("day16.txt").expect("Unable to read input file");
     println!("Part1: {}", part1(input1));
     println!("Part2: {}", part2(input1));
+
+    let maze = Maze::parse(input1);
+    let route = maze.find_route(&Costs::standard()).expect("No route from start to end");
+    println!("Route: {} tiles", route.tiles.len());
+    let shortest = maze.find_route(&Costs { step: 1, turn: 0, reverse: 0 }).expect("No route from start to end");
+    println!("Shortest walk: {} steps", shortest.score);
+
+    let paths = maze.get_optimal_paths(&Costs::standard()).unwrap();
+    println!("Optimal paths: {} scoring {}", paths.count, paths.score);
+    if std::env::args().any(|arg| arg == "--seats") {
+        println!("{}", maze.render(&paths.tiles));
+        let busiest = paths.tile_counts.values().filter(|count| **count == paths.count).count();
+        println!("Tiles on every optimal path: {}", busiest);
+    }
+
+    if std::env::args().any(|arg| arg == "--routes") {
+        for route in maze.find_routes(&Costs::standard(), 5, Some(2000)) {
+            println!("Route scoring {} with {} turns over {} tiles, diverging at {:?}", route.score, route.turns, route.tiles.len(), route.divergence);
+        }
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use std::fs;
-    use crate::part1;
-    use crate::part2;
+    use crate::{part1, part2, BigCount, Costs, Maze};
 
     #[test]
     fn test_part1_small() {
@@ -404,4 +595,173 @@ mod tests {
 
         assert_eq!(part2(&example1.to_string()), expected1);
     }
+
+    #[test]
+    fn test_route() {
+        let example1 = r#"#######
+#...#.#
+#.#...#
+#E#.#S#
+#######"#;
+
+        let maze = Maze::parse(example1);
+        let route = maze.find_route(&Costs::standard()).unwrap();
+        assert_eq!(route.score, 5008);
+        assert_eq!(route.tiles, vec![(3, 5), (2, 5), (2, 4), (2, 3), (1, 3), (1, 2), (1, 1), (2, 1), (3, 1)]);
+        assert_eq!(part1(&example1.to_string()), "5008".to_string());
+
+        let route = maze.find_route(&Costs { step: 1, turn: 0, reverse: 0 }).unwrap();
+        assert_eq!(route.score, 8);
+        assert_eq!(route.tiles.len(), 9);
+
+        let example2 = r#"#####
+#E.S#
+#####"#;
+
+        let maze = Maze::parse(example2);
+        assert_eq!(maze.find_route(&Costs::standard()).unwrap().score, 2002);
+        assert_eq!(maze.find_route(&Costs { step: 1, turn: 1000, reverse: 5 }).unwrap().score, 7);
+        assert_eq!(maze.find_route(&Costs { step: 1, turn: 1, reverse: 5 }).unwrap().score, 4);
+        assert!(Maze::parse("#####\n#E#S#\n#####").find_route(&Costs::standard()).is_none());
+    }
+
+    #[test]
+    fn test_optimal_paths() {
+        let example1 = r#"###############
+#.......#....E#
+#.#.###.#.###.#
+#.....#.#...#.#
+#.###.#####.#.#
+#.#.#.......#.#
+#.#.#####.###.#
+#...........#.#
+###.#.#####.#.#
+#...#.....#.#.#
+#.#.#.###.#.#.#
+#.....#...#.#.#
+#.###.#.#.#.#.#
+#S..#.....#...#
+###############"#;
+        let expected1 = r#"###############
+#.......#....O#
+#.#.###.#.###O#
+#.....#.#...#O#
+#.###.#####.#O#
+#.#.#.......#O#
+#.#.#####.###O#
+#..OOOOOOOOO#O#
+###O#O#####O#O#
+#OOO#O....#O#O#
+#O#O#O###.#O#O#
+#OOOOO#...#O#O#
+#O###.#.#.#O#O#
+#O..#.....#OOO#
+###############"#;
+
+        let maze = Maze::parse(example1);
+        let paths = maze.get_optimal_paths(&Costs::standard()).unwrap();
+        assert_eq!(paths.score, 7036);
+        assert_eq!(paths.tiles.len(), 45);
+        assert_eq!(maze.render(&paths.tiles), expected1);
+
+        let example2 = r#"#######
+#.....#
+#S###E#
+#.....#
+#######"#;
+
+        let paths = Maze::parse(example2).get_optimal_paths(&Costs::standard()).unwrap();
+        assert_eq!(paths.score, 3006);
+        assert_eq!(paths.count, BigCount::from(2));
+        assert_eq!(paths.tiles.len(), 12);
+        assert_eq!(paths.tile_counts[&(2, 1)], BigCount::from(2));
+        assert_eq!(paths.tile_counts[&(1, 3)], BigCount::from(1));
+        assert_eq!(paths.tile_counts[&(3, 3)], BigCount::from(1));
+    }
+
+    #[test]
+    fn test_optimal_paths_reversing() {
+        // Reversing costs the same as two turns, which is still only one way through
+        let example1 = r#"#####
+#E.S#
+#####"#;
+
+        let maze = Maze::parse(example1);
+        let paths = maze.get_optimal_paths(&Costs::standard()).unwrap();
+        assert_eq!(paths.score, 2002);
+        assert_eq!(paths.count, BigCount::from(1));
+        assert_eq!(paths.tile_counts[&(1, 3)], BigCount::from(1));
+        assert_eq!(paths.tiles, vec![(1, 1), (1, 2), (1, 3)]);
+
+        assert_eq!(maze.get_optimal_paths(&Costs { step: 1, turn: 0, reverse: 0 }).unwrap().count, BigCount::from(1));
+        assert!(maze.get_optimal_paths(&Costs { step: 0, turn: 0, reverse: 0 }).is_err());
+        assert!(maze.get_optimal_paths(&Costs { step: 1, turn: -1, reverse: 0 }).is_err());
+        assert!(Maze::parse("#####\n#E#S#\n#####").get_optimal_paths(&Costs::standard()).is_err());
+    }
+
+    #[test]
+    fn test_optimal_path_count_overflow() {
+        // 130 loops in a row, each passable over the top or the bottom for the same score
+        let loops = 130;
+        let outer = "#".repeat(loops * 5 + 2);
+        let side = format!("#{}#", "....#".repeat(loops));
+        let middle = format!("#{}#", ".##..".repeat(loops));
+        let mut middle: Vec<char> = middle.chars().collect();
+        middle[1] = 'S';
+        middle[loops * 5] = 'E';
+        let example1 = [outer.clone(), side.clone(), middle.iter().collect(), side, outer].join("\n");
+
+        let paths = Maze::parse(&example1).get_optimal_paths(&Costs::standard()).unwrap();
+        assert_eq!(paths.count.to_string(), "1361129467683753853853498429727072845824");
+        assert_eq!(paths.tiles.len(), loops * 11);
+        assert_eq!(paths.tile_counts[&(1, 2)].to_string(), "680564733841876926926749214863536422912");
+        assert_eq!(paths.tile_counts[&(2, 5)], paths.count);
+        assert_eq!(BigCount::from(999_999_999_999).multiply(&BigCount::from(999_999_999_999)).to_string(), "999999999998000000000001");
+    }
+
+    #[test]
+    fn test_routes() {
+        let example1 = r#"###############
+#.......#....E#
+#.#.###.#.###.#
+#.....#.#...#.#
+#.###.#####.#.#
+#.#.#.......#.#
+#.#.#####.###.#
+#...........#.#
+###.#.#####.#.#
+#...#.....#.#.#
+#.#.#.###.#.#.#
+#.....#...#.#.#
+#.###.#.#.#.#.#
+#S..#.....#...#
+###############"#;
+
+        let maze = Maze::parse(example1);
+        let routes = maze.find_routes(&Costs::standard(), 10, Some(0));
+        assert_eq!(routes.iter().map(|route| route.score).collect::<Vec<i64>>(), vec![7036, 7036, 7036]);
+        assert!(routes[0].divergence.is_empty());
+        assert!(routes[1..].iter().all(|route| !route.divergence.is_empty()));
+        assert_eq!(routes[0].turns, maze.find_route(&Costs::standard()).unwrap().turns);
+
+        let routes = maze.find_routes(&Costs::standard(), 5, None);
+        assert_eq!(routes.len(), 5);
+        assert!(routes.windows(2).all(|pair| pair[0].score <= pair[1].score));
+        assert!(routes[3].score > 7036);
+        assert!(maze.find_routes(&Costs::standard(), 0, None).is_empty());
+
+        let example2 = r#"#######
+#.....#
+#S###E#
+#.....#
+#######"#;
+
+        let maze = Maze::parse(example2);
+        let routes = maze.find_routes(&Costs::standard(), 3, None);
+        assert_eq!(routes.iter().map(|route| route.score).collect::<Vec<i64>>()[..2], [3006, 3006]);
+        assert!(routes[2].score > 3006);
+        assert_eq!(routes[0].turns, 3);
+        assert_eq!(routes[1].turns, 3);
+        assert_eq!(routes[1].divergence, vec![(2, 1)]);
+    }
 }
@@ -1,121 +1,728 @@
-fn run(mut a: u64, mut b: u64, mut c: u64, program: &Vec<&str>) -> Option<String> {
-    let mut output: Vec<u64> = Vec::new();
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::io::{BufRead, Write};
+use std::ops::Range;
 
+// Guards run against programs that never halt, like 3,0 with a non-zero A.
+const RUN_STEP_LIMIT: usize = 10_000_000;
+
+#[derive(Debug, PartialEq)]
+enum Stop {
+    Halted,
+    Breakpoint(usize),
+    StepLimit,
+    InvalidOperand(usize)
+}
+
+struct Debugger {
+    a: u64,
+    b: u64,
+    c: u64,
+    pc: usize,
+    program: Vec<u8>,
+    output: Vec<u8>,
+    steps: usize,
+    step_limit: Option<usize>,
+    breakpoints: HashSet<usize>,
+    watches: Vec<char>
+}
+
+impl Debugger {
+    fn new(a: u64, b: u64, c: u64, program: &[u8]) -> Debugger {
+        Debugger {
+            a, b, c,
+            pc: 0,
+            program: program.to_vec(),
+            output: Vec::new(),
+            steps: 0,
+            step_limit: None,
+            breakpoints: HashSet::new(),
+            watches: Vec::new()
+        }
+    }
+
+    fn get_combo(&self, operand: u8) -> Option<u64> {
+        match operand {
+            0..=3 => Some(operand as u64),
+            4 => Some(self.a),
+            5 => Some(self.b),
+            6 => Some(self.c),
+            _ => None,
+        }
+    }
+
+    // Executes one instruction, or says why it can't.
+    fn step(&mut self) -> Option<Stop> {
+        if self.pc + 1 >= self.program.len() {
+            return Some(Stop::Halted)
+        }
+        if self.step_limit.is_some_and(|limit| self.steps >= limit) {
+            return Some(Stop::StepLimit)
+        }
+
+        let (opcode, operand) = (self.program[self.pc], self.program[self.pc + 1]);
+        let combo = match opcode {
+            1 | 3 | 4 => operand as u64,
+            _ => match self.get_combo(operand) {
+                None => return Some(Stop::InvalidOperand(self.pc)),
+                Some(combo) => combo,
+            },
+        };
+        let divide = |value: u64| if combo >= 64 { 0 } else { value >> combo };
+
+        self.pc += 2;
+        match opcode {
+            0 => self.a = divide(self.a),
+            1 => self.b ^= combo,
+            2 => self.b = combo % 8,
+            3 => if self.a != 0 { self.pc = combo as usize },
+            4 => self.b ^= self.c,
+            5 => self.output.push((combo % 8) as u8),
+            6 => self.b = divide(self.a),
+            _ => self.c = divide(self.a),
+        }
+        self.steps += 1;
+
+        None
+    }
+
+    // Runs until the program halts or reaches a breakpoint, always taking at least one step so it can resume.
+    fn run(&mut self) -> Stop {
+        loop {
+            if let Some(stop) = self.step() {
+                return stop
+            }
+            if self.breakpoints.contains(&self.pc) {
+                return Stop::Breakpoint(self.pc)
+            }
+        }
+    }
+
+    fn get_output(&self) -> String {
+        format_program(&self.output)
+    }
+
+    fn snapshot(&self) -> Machine {
+        Machine { a: self.a, b: self.b, c: self.c, program: self.program.clone() }
+    }
+
+    fn get_watches(&self) -> String {
+        self.watches.iter().map(|register| match register {
+            'A' => format!("A={}", self.a),
+            'B' => format!("B={}", self.b),
+            _ => format!("C={}", self.c),
+        }).collect::<Vec<String>>().join(" ")
+    }
+
+    fn get_status(&self) -> String {
+        let next = match self.program.get(self.pc..self.pc + 2) {
+            Some(pair) => describe_instruction(pair[0], pair[1]),
+            None => "halted".to_string(),
+        };
+        format!("pc={:02} steps={} next: {}", self.pc, self.steps, next)
+    }
+}
+
+fn execute(a: u64, b: u64, c: u64, program: &[u8]) -> Option<Vec<u8>> {
+    let mut debugger = Debugger::new(a, b, c, program);
+    debugger.step_limit = Some(RUN_STEP_LIMIT);
+
+    match debugger.run() {
+        Stop::Halted => Some(debugger.output),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Combo {
+    Literal(u64),
+    A,
+    B,
+    C
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Instruction {
+    Adv(Combo),
+    Bxl(u64),
+    Bst(Combo),
+    Jnz(usize),
+    Bxc,
+    Out(Combo),
+    Bdv(Combo),
+    Cdv(Combo),
+    Invalid
+}
+
+// Decodes the instruction starting at every pc, not just the even ones, so jumps to odd addresses still work.
+fn compile(program: &[u8]) -> Vec<Instruction> {
+    program.windows(2).map(|pair| {
+        let combo = match pair[1] {
+            0..=3 => Combo::Literal(pair[1] as u64),
+            4 => Combo::A,
+            5 => Combo::B,
+            6 => Combo::C,
+            _ if matches!(pair[0], 1 | 3 | 4) => Combo::Literal(7),
+            _ => return Instruction::Invalid,
+        };
+        match pair[0] {
+            0 => Instruction::Adv(combo),
+            1 => Instruction::Bxl(pair[1] as u64),
+            2 => Instruction::Bst(combo),
+            3 => Instruction::Jnz(pair[1] as usize),
+            4 => Instruction::Bxc,
+            5 => Instruction::Out(combo),
+            6 => Instruction::Bdv(combo),
+            _ => Instruction::Cdv(combo),
+        }
+    }).collect()
+}
+
+// Same results as execute, but fills the given buffer so a batch doesn't allocate for every A. False if it doesn't halt.
+fn execute_compiled(instructions: &[Instruction], a: u64, b: u64, c: u64, output: &mut Vec<u8>) -> bool {
+    let (mut a, mut b, mut c) = (a, b, c);
     let mut pc = 0;
+    output.clear();
+
+    for _ in 0..RUN_STEP_LIMIT {
+        let Some(instruction) = instructions.get(pc) else {
+            return true
+        };
+        let value = |combo: Combo| match combo {
+            Combo::Literal(value) => value,
+            Combo::A => a,
+            Combo::B => b,
+            Combo::C => c,
+        };
+        let divide = |combo: Combo| if value(combo) >= 64 { 0 } else { a >> value(combo) };
+
+        pc += 2;
+        match *instruction {
+            Instruction::Adv(combo) => a = divide(combo),
+            Instruction::Bxl(literal) => b ^= literal,
+            Instruction::Bst(combo) => b = value(combo) % 8,
+            Instruction::Jnz(target) => if a != 0 { pc = target },
+            Instruction::Bxc => b ^= c,
+            Instruction::Out(combo) => output.push((value(combo) % 8) as u8),
+            Instruction::Bdv(combo) => b = divide(combo),
+            Instruction::Cdv(combo) => c = divide(combo),
+            Instruction::Invalid => return false,
+        }
+    }
+
+    false
+}
+
+// Runs every A in the range, split into one contiguous chunk per thread, and returns the halting runs that keep
+// accepts in order of A. Passing a filter lets millions of runs through without holding on to every output.
+fn execute_batch(instructions: &[Instruction], values: Range<u64>, b: u64, c: u64, threads: usize, keep: impl Fn(u64, &[u8]) -> bool + Sync) -> Vec<(u64, Vec<u8>)> {
+    let chunk = (values.end.saturating_sub(values.start)).div_ceil(threads.max(1) as u64).max(1);
+    let run_chunk = |start: u64| {
+        let mut output = Vec::new();
+        let mut results = Vec::new();
+        for a in start..(start.saturating_add(chunk)).min(values.end) {
+            if execute_compiled(instructions, a, b, c, &mut output) && keep(a, &output) {
+                results.push((a, output.clone()));
+            }
+        }
+        results
+    };
 
-    while pc < program.len() {
-        let instruction = program[pc];
-        let raw_operand = program[pc + 1].parse::<u64>().unwrap();
-        let operand = match instruction {
-            "1" | "3" | "4" => raw_operand,
-            _ => {
-                match raw_operand {
-                    0 | 1 | 2 | 3 => raw_operand,
-                    4 => a,
-                    5 => b,
-                    6 => c,
-                    _ => return None
+    std::thread::scope(|scope| {
+        let handles = values.clone().step_by(chunk as usize).map(|start| scope.spawn(move || run_chunk(start))).collect::<Vec<_>>();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    })
+}
+
+fn format_program(values: &[u8]) -> String {
+    values.iter().map(ToString::to_string).collect::<Vec<String>>().join(",")
+}
+
+#[derive(Debug, PartialEq)]
+struct Machine {
+    a: u64,
+    b: u64,
+    c: u64,
+    program: Vec<u8>
+}
+
+impl FromStr for Machine {
+    type Err = String;
+
+    // Lines can come in any order with blank lines anywhere, and a missing B or C register starts at 0
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut registers: [Option<u64>; 3] = [None; 3];
+        let mut program: Option<Vec<u8>> = None;
+
+        for line in input.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (key, value) = line.split_once(':').ok_or(format!("Expected 'name: value' in '{}'", line))?;
+            let (key, value) = (key.trim(), value.trim());
+            if key == "Program" {
+                if program.is_some() {
+                    return Err("Program given twice".to_string())
                 }
+                program = Some(parse_program(value)?);
+                continue;
             }
-        };
-        match instruction {
-            "0" => {
-                let denominator = 2_u64.pow(operand as u32);
-                a = a / denominator;
-                pc += 2;
-            },
-            "1" => {
-                b = operand ^ b;
-                pc += 2;
-            },
-            "2" => {
-                b = operand % 8;
-                pc += 2;
-            },
-            "3" => {
-                pc = if a == 0 { pc + 2 } else { operand as usize };
+
+            let index = match key {
+                "Register A" => 0,
+                "Register B" => 1,
+                "Register C" => 2,
+                _ => return Err(format!("Unknown line '{}'", line)),
+            };
+            if registers[index].is_some() {
+                return Err(format!("{} given twice", key))
+            }
+            registers[index] = Some(value.parse::<u64>().map_err(|_| format!("Bad value for {}: '{}'", key, value))?);
+        }
+
+        let program = program.ok_or("Missing program")?;
+        if program.len() % 2 != 0 {
+            return Err("Program has an opcode without an operand".to_string())
+        }
+        for (index, pair) in program.chunks(2).enumerate() {
+            // Only bxl, jnz and bxc take a literal operand
+            if !matches!(pair[0], 1 | 3 | 4) && pair[1] == 7 {
+                return Err(format!("Reserved combo operand 7 at {}", index * 2))
             }
-            "4" => {
-                b = b ^ c;
-                pc += 2;
+        }
+
+        Ok(Machine {
+            a: registers[0].ok_or("Missing Register A")?,
+            b: registers[1].unwrap_or(0),
+            c: registers[2].unwrap_or(0),
+            program
+        })
+    }
+}
+
+impl fmt::Display for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Register A: {}\nRegister B: {}\nRegister C: {}\n\nProgram: {}", self.a, self.b, self.c, format_program(&self.program))
+    }
+}
+
+impl Machine {
+    fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, format!("{}\n", self))
+    }
+}
+
+fn debug_session(debugger: &mut Debugger, input: impl BufRead, output: &mut impl Write) {
+    writeln!(output, "{}", debugger.get_status()).unwrap();
+    for line in input.lines() {
+        let line = line.unwrap();
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let argument = words.next();
+        match (command, argument) {
+            ("s" | "step", count) => match count.map_or(Ok(1), |count| count.parse::<usize>()) {
+                Ok(count) => for _ in 0..count {
+                    if let Some(stop) = debugger.step() {
+                        writeln!(output, "Stopped: {:?}", stop).unwrap();
+                        break;
+                    }
+                },
+                Err(_) => writeln!(output, "Bad step count {}", count.unwrap()).unwrap(),
             },
-            "5" => {
-                output.push(operand % 8);
-                pc += 2;
+            ("c" | "continue", _) => writeln!(output, "Stopped: {:?}", debugger.run()).unwrap(),
+            ("b" | "break", Some(pc)) => match pc.parse::<usize>() {
+                Ok(pc) => if !debugger.breakpoints.remove(&pc) { debugger.breakpoints.insert(pc); },
+                Err(_) => writeln!(output, "Bad address {}", pc).unwrap(),
             },
-            "6" => {
-                let denominator = 2_u64.pow(operand as u32);
-                b = a / denominator;
-                pc += 2;
+            ("w" | "watch", Some(register)) => match register.to_uppercase().as_str() {
+                "A" | "B" | "C" => debugger.watches.push(register.to_uppercase().chars().next().unwrap()),
+                _ => writeln!(output, "Unknown register {}", register).unwrap(),
             },
-            "7" => {
-                let denominator = 2_u64.pow(operand as u32);
-                c = a / denominator;
-                pc += 2;
+            ("l" | "limit", Some("none")) => debugger.step_limit = None,
+            ("l" | "limit", Some(limit)) => match limit.parse::<usize>() {
+                Ok(limit) => debugger.step_limit = Some(limit),
+                Err(_) => writeln!(output, "Bad step limit {}", limit).unwrap(),
             },
-            _ => {
-                return None
-            }
+            ("o" | "output", _) => writeln!(output, "Output: {}", debugger.get_output()).unwrap(),
+            ("r" | "registers", _) => writeln!(output, "A={} B={} C={}", debugger.a, debugger.b, debugger.c).unwrap(),
+            ("save", Some(path)) => {
+                match debugger.snapshot().save(path) {
+                    Ok(()) => writeln!(output, "Saved machine to {}", path).unwrap(),
+                    Err(error) => writeln!(output, "Unable to save to {}: {}", path, error).unwrap(),
+                }
+            },
+            ("q" | "quit", _) => return,
+            _ => writeln!(output, "Commands: step [n], continue, break <pc>, watch <A|B|C>, limit <n|none>, output, registers, save <path>, quit").unwrap(),
+        }
+        writeln!(output, "{}", debugger.get_status()).unwrap();
+        if !debugger.watches.is_empty() {
+            writeln!(output, "{}", debugger.get_watches()).unwrap();
         }
     }
-
-    Some(format!("{}", output.iter().map(ToString::to_string).collect::<Vec<String>>().join(",")))
 }
 
 fn part1(input: &String) -> String {
-    let a = input.lines().nth(0).unwrap().split(": ").nth(1).unwrap().parse::<u64>().unwrap();
-    let b = input.lines().nth(1).unwrap().split(": ").nth(1).unwrap().parse::<u64>().unwrap();
-    let c = input.lines().nth(2).unwrap().split(": ").nth(1).unwrap().parse::<u64>().unwrap();
-    let program = input.lines().nth(4).unwrap().split(": ").nth(1).unwrap().split(",").collect::<Vec<&str>>();
+    let machine = input.parse::<Machine>().unwrap();
+
+    format_program(&execute(machine.a, machine.b, machine.c, &machine.program).unwrap())
+}
+
+#[derive(Debug, PartialEq)]
+enum SearchMethod {
+    Structural,
+    BitLevel
+}
+
+// The usual shape of these programs: a single loop ending in jnz 0 that shifts A right by exactly 3 bits once,
+// outputs once, and works B and C out from A afresh each time round. Then every output only depends on the
+// top bits of A that are left, and A can be built up 3 bits at a time from the last output backwards.
+fn is_three_bit_loop(program: &[u8]) -> bool {
+    if program.len() < 4 || !program.len().is_multiple_of(2) || program[program.len() - 2..] != [3, 0] {
+        return false
+    }
+
+    let body: Vec<(u8, u8)> = program[..program.len() - 2].chunks(2).map(|pair| (pair[0], pair[1])).collect();
+    let count = |opcode: u8| body.iter().filter(|(known, _)| *known == opcode).count();
+    if count(3) != 0 || count(5) != 1 || count(0) != 1 || !body.contains(&(0, 3)) {
+        return false
+    }
+
+    let (mut has_b, mut has_c) = (false, false);
+    for (opcode, operand) in body {
+        let reads_combo = !matches!(opcode, 1 | 3 | 4);
+        let reads_b = opcode == 1 || opcode == 4 || (reads_combo && operand == 5);
+        let reads_c = opcode == 4 || (reads_combo && operand == 6);
+        if (reads_b && !has_b) || (reads_c && !has_c) || (reads_combo && operand == 7) {
+            return false
+        }
+        match opcode {
+            2 | 6 => has_b = true,
+            7 => has_c = true,
+            _ => {},
+        }
+    }
+    true
+}
+
+fn search_three_bit_loop(program: &[u8], target: &[u8], b: u64, c: u64) -> Vec<u64> {
+    let instructions = compile(program);
+    let mut output = Vec::new();
+    let mut candidates: Vec<u64> = vec![0];
+    for depth in 1..=target.len() {
+        let suffix = &target[target.len() - depth..];
+        candidates = candidates.iter()
+            .flat_map(|a| (a << 3..(a << 3) + 8).filter(|guess| *guess != 0 || depth == target.len()))
+            .filter(|guess| execute_compiled(&instructions, *guess, b, c, &mut output) && output == suffix)
+            .collect();
+    }
+    candidates
+}
+
+// A register where each bit is either known or not, for running a program while only some bits of A are decided.
+#[derive(Clone, Copy)]
+struct PartialValue {
+    known: u64,
+    value: u64
+}
+
+impl PartialValue {
+    fn exact(value: u64) -> PartialValue {
+        PartialValue { known: u64::MAX, value }
+    }
+
+    fn shift_right(&self, amount: PartialValue) -> PartialValue {
+        if amount.known != u64::MAX {
+            return PartialValue { known: 0, value: 0 }
+        }
+        if amount.value >= 64 {
+            return PartialValue::exact(0)
+        }
+        PartialValue { known: (self.known >> amount.value) | !(u64::MAX >> amount.value), value: self.value >> amount.value }
+    }
+
+    fn xor(&self, other: PartialValue) -> PartialValue {
+        let known = self.known & other.known;
+        PartialValue { known, value: (self.value ^ other.value) & known }
+    }
 
-    run(a, b, c, &program).unwrap()
+    fn low_bits(&self) -> PartialValue {
+        PartialValue { known: self.known | !7, value: self.value & 7 }
+    }
 }
 
-fn solve(a: u64, program: &Vec<&str>, depth: usize) -> Option<u64> {
-    let solution_at_depth = program[program.len() - depth..program.len()].join(",");
+// Runs as far as the known bits allow. False means the program certainly can't produce the target.
+fn is_consistent(a: PartialValue, b: u64, c: u64, program: &[u8], target: &[u8]) -> bool {
+    let (mut a, mut b, mut c) = (a, PartialValue::exact(b), PartialValue::exact(c));
+    let mut pc = 0;
+    let mut printed = 0;
 
-    for guess in a..a + 8 {
-        if guess == 0 {
-            continue
+    for _ in 0..RUN_STEP_LIMIT {
+        if pc + 1 >= program.len() {
+            return printed == target.len()
         }
+        let (opcode, operand) = (program[pc], program[pc + 1]);
+        let combo = match operand {
+            0..=3 => PartialValue::exact(operand as u64),
+            4 => a,
+            5 => b,
+            6 => c,
+            _ if matches!(opcode, 1 | 3 | 4) => PartialValue::exact(operand as u64),
+            _ => return false,
+        };
+        let literal = PartialValue::exact(operand as u64);
 
-        if run(guess, 0, 0, &program) == Some(solution_at_depth.clone()) {
-            if depth == program.len() {
-                return Some(guess)
-            } else {
-                let candidate = solve(guess * 8, program, depth + 1);
-                if candidate.is_some() {
-                    return candidate;
+        pc += 2;
+        match opcode {
+            0 => a = a.shift_right(combo),
+            1 => b = b.xor(literal),
+            2 => b = combo.low_bits(),
+            3 => {
+                if a.value != 0 {
+                    pc = operand as usize;
+                } else if a.known != u64::MAX {
+                    return true
                 }
-            }
+            },
+            4 => b = b.xor(c),
+            5 => {
+                let digit = combo.low_bits();
+                if printed >= target.len() || (digit.known & 7 == 7 && digit.value != target[printed] as u64) {
+                    return false
+                }
+                if digit.known & 7 != 7 {
+                    return true
+                }
+                printed += 1;
+            },
+            6 => b = a.shift_right(combo),
+            _ => c = a.shift_right(combo),
+        }
+    }
+
+    true
+}
+
+struct BitSearch<'a> {
+    program: &'a [u8],
+    instructions: &'a [Instruction],
+    target: &'a [u8],
+    b: u64,
+    c: u64,
+    max_bits: u32
+}
+
+impl BitSearch<'_> {
+    // Decides A one bit at a time from the bottom, dropping a branch as soon as the decided bits rule out the target.
+    fn search(&self, bits: u32, a: u64, candidates: &mut Vec<u64>, output: &mut Vec<u8>) {
+        let open = if self.max_bits >= 64 { u64::MAX } else { (1_u64 << self.max_bits) - 1 } & !((1_u64 << bits) - 1);
+        if !is_consistent(PartialValue { known: !open, value: a }, self.b, self.c, self.program, self.target) {
+            return
+        }
+        if (bits == 0 || a >> (bits - 1) == 1) && execute_compiled(self.instructions, a, self.b, self.c, output) && output == self.target {
+            candidates.push(a);
+        }
+        if bits < self.max_bits.min(63) {
+            self.search(bits + 1, a, candidates, output);
+            self.search(bits + 1, a | (1 << bits), candidates, output);
         }
     }
+}
+
+// Every A below 2^max_bits that makes the program print exactly the target, smallest first.
+fn find_register_a(program: &[u8], target: &[u8], b: u64, c: u64, max_bits: u32) -> (SearchMethod, Vec<u64>) {
+    let (method, mut candidates) = if is_three_bit_loop(program) {
+        (SearchMethod::Structural, search_three_bit_loop(program, target, b, c))
+    } else {
+        let mut candidates = Vec::new();
+        BitSearch { program, instructions: &compile(program), target, b, c, max_bits }.search(0, 0, &mut candidates, &mut Vec::new());
+        (SearchMethod::BitLevel, candidates)
+    };
+    candidates.retain(|a| *a < 1_u64.checked_shl(max_bits).unwrap_or(u64::MAX) || max_bits >= 64);
+    candidates.sort();
+    candidates.dedup();
+    (method, candidates)
+}
 
-    None
+// Each output usually consumes 3 bits of A, so a few bits past that covers the answer without letting the bit-level
+// search wander through all 64.
+fn get_bit_limit(target: &[u8]) -> u32 {
+    (3 * target.len() as u32 + 3).min(64)
 }
 
 fn part2(input: &String) -> String {
-    let original_program = input.lines().nth(4).unwrap();
-    let program = original_program.split(": ").nth(1).unwrap().split(",").collect::<Vec<&str>>();
+    let machine = input.parse::<Machine>().unwrap();
 
-    format!("{}", solve(0, &program, 1).unwrap())
+    match find_register_a(&machine.program, &machine.program, machine.b, machine.c, get_bit_limit(&machine.program)).1.first() {
+        Some(a) => format!("{}", a),
+        None => format!("No A below 2^{} prints the program", get_bit_limit(&machine.program)),
+    }
+}
+
+const MNEMONICS: [&str; 8] = ["adv", "bxl", "bst", "jnz", "bxc", "out", "bdv", "cdv"];
+
+fn parse_program(text: &str) -> Result<Vec<u8>, String> {
+    let mut program: Vec<u8> = Vec::new();
+    for value in text.trim().split(',').map(str::trim) {
+        let value = value.parse::<u8>().map_err(|_| format!("Bad program value '{}'", value))?;
+        if value > 7 {
+            return Err(format!("Value at {} is not 3 bits", program.len()))
+        }
+        program.push(value);
+    }
+    Ok(program)
+}
+
+fn describe_combo(operand: u8) -> String {
+    match operand {
+        0..=3 => format!("combo({})={}", operand, operand),
+        4 => "combo(4)=A".to_string(),
+        5 => "combo(5)=B".to_string(),
+        6 => "combo(6)=C".to_string(),
+        _ => format!("combo({})=invalid", operand),
+    }
+}
+
+fn describe_instruction(opcode: u8, operand: u8) -> String {
+    match opcode {
+        0 => format!("adv A, {}", describe_combo(operand)),
+        1 => format!("bxl B, {}", operand),
+        2 => format!("bst B, {}", describe_combo(operand)),
+        3 => format!("jnz {}", operand),
+        4 => format!("bxc B, C (operand {})", operand),
+        5 => format!("out {}", describe_combo(operand)),
+        6 => format!("bdv B, {}", describe_combo(operand)),
+        7 => format!("cdv C, {}", describe_combo(operand)),
+        _ => format!("??? {},{}", opcode, operand),
+    }
+}
+
+// A value left over at the end without an operand is listed as data, so the listing assembles back to the same program.
+fn disassemble(program: &str) -> Result<String, String> {
+    let program = parse_program(program)?;
+    let mut lines: Vec<String> = Vec::new();
+
+    for (pc, pair) in program.chunks(2).enumerate() {
+        lines.push(match pair {
+            [opcode, operand] => format!("{:02}: {}", pc * 2, describe_instruction(*opcode, *operand)),
+            _ => format!("{:02}: data {}", pc * 2, pair[0]),
+        });
+    }
+
+    Ok(lines.join("\n"))
+}
+
+// Reads listings in the disassembler's format, but also hand-written ones where a combo operand is just a number
+// 0-3 or a register name, addresses are optional and anything after ';' is a comment. 'data n' is a lone value.
+fn assemble(source: &str) -> Result<String, String> {
+    let mut program: Vec<String> = Vec::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line = line.split(';').next().unwrap().trim();
+        let line = match line.split_once(':') {
+            Some((address, rest)) if address.trim().chars().all(|c| c.is_ascii_digit()) => rest.trim(),
+            _ => line,
+        };
+        if line.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, arguments) = line.split_once(' ').unwrap_or((line, ""));
+        let error = |message: &str| format!("Line {}: {} in '{}'", line_index + 1, message, line);
+        if mnemonic.to_lowercase() == "data" {
+            match arguments.trim().parse::<u8>() {
+                Ok(value) if value <= 7 => program.push(format!("{}", value)),
+                _ => return Err(error("data is not 3 bits")),
+            }
+            continue;
+        }
+        let opcode = MNEMONICS.iter().position(|known| *known == mnemonic.to_lowercase()).ok_or_else(|| error("unknown mnemonic"))?;
+        let arguments: Vec<&str> = arguments.split(',').map(str::trim).filter(|argument| !argument.is_empty()).collect();
+
+        let operand = match opcode {
+            1 | 3 => arguments.last().and_then(|argument| argument.parse::<u8>().ok()),
+            4 => Some(arguments.last().and_then(|argument| argument.strip_prefix("C (operand "))
+                .and_then(|argument| argument.strip_suffix(')'))
+                .map_or(Ok(0), |argument| argument.parse::<u8>())
+                .map_err(|_| error("bad ignored operand"))?),
+            // The disassembler's combo(n) spells out the operand itself, anything else has to read as a combo value.
+            _ => match arguments.last() {
+                None => None,
+                Some(argument) => Some(match argument.strip_prefix("combo(") {
+                    Some(rest) => rest.split(')').next().unwrap().parse::<u8>().map_err(|_| error("bad combo operand"))?,
+                    None => match *argument {
+                        "A" => 4,
+                        "B" => 5,
+                        "C" => 6,
+                        number => number.parse::<u8>().ok()
+                            .filter(|number| *number <= 3)
+                            .ok_or_else(|| error("combo operand is not 0-3 or a register"))?,
+                    },
+                }),
+            },
+        }.ok_or_else(|| error("missing operand"))?;
+        if operand > 7 {
+            return Err(error("operand is not 3 bits"))
+        }
+
+        program.push(format!("{},{}", opcode, operand));
+    }
+
+    Ok(program.join(","))
 }
 
 fn main() {
     env_logger::init();
-    let input = r#"Register A: 61156655
-Register B: 0
-Register C: 0
+    let args: Vec<String> = std::env::args().collect();
+    let input = &if args.iter().any(|arg| arg == "--stdin") {
+        std::io::read_to_string(std::io::stdin()).expect("Unable to read stdin")
+    } else {
+        std::fs::read_to_string("day17.txt").expect("Unable to read input file")
+    };
+    let machine = input.parse::<Machine>().unwrap_or_else(|message| panic!("Invalid machine: {}", message));
 
-Program: 2,4,1,5,7,5,4,3,1,6,0,3,5,5,3,0"#;
+    println!("Part1: {}", part1(input));
+    println!("Part2: {}", part2(input));
 
-    println!("Part1: {}", part1(&input.to_string()));
-    println!("Part2: {}", part2(&input.to_string()));
+    if args.iter().any(|arg| arg == "--disassemble") {
+        println!("{}", disassemble(&format_program(&machine.program)).unwrap());
+    }
+    let target = args.iter().position(|arg| arg == "--target").and_then(|index| args.get(index + 1))
+        .map(|target| parse_program(target))
+        .transpose();
+    match &target {
+        Err(message) => println!("Invalid target: {}", message),
+        Ok(Some(target)) => {
+            let (method, candidates) = find_register_a(&machine.program, target, machine.b, machine.c, get_bit_limit(target));
+            println!("{:?} search found {} candidates: {:?}", method, candidates.len(), candidates);
+        },
+        Ok(None) => {},
+    }
+    if let (Some(limit), Ok(target)) = (args.iter().position(|arg| arg == "--brute").and_then(|index| args.get(index + 1)), &target) {
+        match limit.parse::<u64>() {
+            Ok(count) => {
+                let target = target.clone().unwrap_or(machine.program.clone());
+                let threads = std::thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+                let started = std::time::Instant::now();
+                let found = execute_batch(&compile(&machine.program), 0..count, machine.b, machine.c, threads, |_, output| output == target);
+                println!("Brute force over {} values on {} threads took {:?}: {:?}", count, threads, started.elapsed(), found.iter().map(|(a, _)| *a).collect::<Vec<u64>>());
+            },
+            Err(_) => println!("Usage: --brute <count of A values>, got '{}'", limit),
+        }
+    }
+    if args.iter().any(|arg| arg == "--debug") {
+        let mut debugger = Debugger::new(machine.a, machine.b, machine.c, &machine.program);
+        debug_session(&mut debugger, std::io::stdin().lock(), &mut std::io::stdout());
+    }
+    if let Some(path) = args.iter().position(|arg| arg == "--assemble").and_then(|index| args.get(index + 1)) {
+        let source = std::fs::read_to_string(path).expect("Unable to read source file");
+        match assemble(&source) {
+            Ok(program) => println!("Program: {}", program),
+            Err(message) => println!("{}", message),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::part1;
+    use crate::{assemble, compile, debug_session, disassemble, execute, execute_batch, execute_compiled, find_register_a, parse_program, part1, part2, Combo, Debugger, Instruction, Machine, SearchMethod, Stop};
 
     #[test]
     fn test_part1() {
@@ -140,4 +747,229 @@ Program: 2,4,1,5,7,5,4,3,1,6,0,3,5,5,3,0"#;
 
         assert_eq!(part1(&example1.to_string()), expected1);
     }
+
+    #[test]
+    fn test_disassemble() {
+        let program = "2,4,1,5,7,5,4,3,1,6,0,3,5,5,3,0";
+        let expected1 = r#"00: bst B, combo(4)=A
+02: bxl B, 5
+04: cdv C, combo(5)=B
+06: bxc B, C (operand 3)
+08: bxl B, 6
+10: adv A, combo(3)=3
+12: out combo(5)=B
+14: jnz 0"#;
+
+        assert_eq!(disassemble(program), Ok(expected1.to_string()));
+        assert_eq!(assemble(expected1), Ok(program.to_string()));
+        assert_eq!(assemble(&disassemble("0,7,4,0,6,6").unwrap()), Ok("0,7,4,0,6,6".to_string()));
+
+        assert_eq!(disassemble("0,1,5"), Ok("00: adv A, combo(1)=1\n02: data 5".to_string()));
+        assert_eq!(assemble(&disassemble("0,1,5").unwrap()), Ok("0,1,5".to_string()));
+        assert_eq!(disassemble("0,1,x"), Err("Bad program value 'x'".to_string()));
+        assert_eq!(disassemble("0,1,9"), Err("Value at 2 is not 3 bits".to_string()));
+        assert_eq!(parse_program("3, 0"), Ok(vec![3, 0]));
+    }
+
+    #[test]
+    fn test_assemble() {
+        let source = r#"; the first example program
+adv A, 1
+out A      ; print the low bits of A
+jnz 0
+"#;
+
+        assert_eq!(assemble(source), Ok("0,1,5,4,3,0".to_string()));
+        assert_eq!(part1(&format!("Register A: 729\nRegister B: 0\nRegister C: 0\n\nProgram: {}", assemble(source).unwrap())), "4,6,3,5,6,3,5,2,1,0".to_string());
+        assert_eq!(assemble("mul A, 2"), Err("Line 1: unknown mnemonic in 'mul A, 2'".to_string()));
+        assert_eq!(assemble("bxl B, 9"), Err("Line 1: operand is not 3 bits in 'bxl B, 9'".to_string()));
+        assert_eq!(assemble("out"), Err("Line 1: missing operand in 'out'".to_string()));
+        assert_eq!(assemble("adv A, 5"), Err("Line 1: combo operand is not 0-3 or a register in 'adv A, 5'".to_string()));
+        assert_eq!(assemble("adv A, B\nout combo(6)=C\nbxl B, 7"), Ok("0,5,5,6,1,7".to_string()));
+        assert_eq!(assemble("data 8"), Err("Line 1: data is not 3 bits in 'data 8'".to_string()));
+    }
+
+    #[test]
+    fn test_debugger() {
+        let mut debugger = Debugger::new(729, 0, 0, &parse_program("0,1,5,4,3,0").unwrap());
+        assert_eq!(debugger.step(), None);
+        assert_eq!((debugger.pc, debugger.a), (2, 364));
+
+        debugger.breakpoints.insert(4);
+        assert_eq!(debugger.run(), Stop::Breakpoint(4));
+        assert_eq!(debugger.get_output(), "4");
+        assert_eq!(debugger.run(), Stop::Breakpoint(4));
+        assert_eq!(debugger.get_output(), "4,6");
+        debugger.breakpoints.clear();
+        assert_eq!(debugger.run(), Stop::Halted);
+        assert_eq!(debugger.get_output(), "4,6,3,5,6,3,5,2,1,0");
+
+        let mut debugger = Debugger::new(1, 0, 0, &parse_program("3,0").unwrap());
+        debugger.step_limit = Some(100);
+        assert_eq!(debugger.run(), Stop::StepLimit);
+        assert_eq!(debugger.steps, 100);
+        assert_eq!(execute(1, 0, 0, &[3, 0]), None);
+        assert_eq!(Debugger::new(1, 0, 0, &parse_program("1,7,0,7").unwrap()).run(), Stop::InvalidOperand(2));
+    }
+
+    #[test]
+    fn test_debug_session() {
+        let mut debugger = Debugger::new(729, 0, 0, &parse_program("0,1,5,4,3,0").unwrap());
+        let mut output: Vec<u8> = Vec::new();
+        debug_session(&mut debugger, "w A\nb 4\nc\nc\no\nb 4\nl 10\nc\nq\ns".as_bytes(), &mut output);
+
+        let expected1 = r#"pc=00 steps=0 next: adv A, combo(1)=1
+pc=00 steps=0 next: adv A, combo(1)=1
+A=729
+pc=00 steps=0 next: adv A, combo(1)=1
+A=729
+Stopped: Breakpoint(4)
+pc=04 steps=2 next: jnz 0
+A=364
+Stopped: Breakpoint(4)
+pc=04 steps=5 next: jnz 0
+A=182
+Output: 4,6
+pc=04 steps=5 next: jnz 0
+A=182
+pc=04 steps=5 next: jnz 0
+A=182
+pc=04 steps=5 next: jnz 0
+A=182
+Stopped: StepLimit
+pc=02 steps=10 next: out combo(4)=A
+A=45"#;
+        assert_eq!(String::from_utf8(output).unwrap().trim_end(), expected1);
+
+        // A typo keeps the old limit rather than letting 3,0 loop forever
+        let mut debugger = Debugger::new(1, 0, 0, &[3, 0]);
+        let mut output: Vec<u8> = Vec::new();
+        debug_session(&mut debugger, "l 50\nl 1o\nc".as_bytes(), &mut output);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Bad step limit 1o"));
+        assert!(output.trim_end().ends_with("Stopped: StepLimit\npc=00 steps=50 next: jnz 0"));
+
+        // A typo in the step count doesn't step at all
+        let mut debugger = Debugger::new(1, 0, 0, &[3, 0]);
+        let mut output: Vec<u8> = Vec::new();
+        debug_session(&mut debugger, "s 1o\ns 2".as_bytes(), &mut output);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Bad step count 1o\npc=00 steps=0 next: jnz 0"));
+        assert!(output.trim_end().ends_with("pc=00 steps=2 next: jnz 0"));
+
+        // A failed save is reported and the session carries on
+        let mut debugger = Debugger::new(1, 0, 0, &[3, 0]);
+        let mut output: Vec<u8> = Vec::new();
+        debug_session(&mut debugger, "save /nonexistent/machine.txt\nr".as_bytes(), &mut output);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Unable to save to /nonexistent/machine.txt: "));
+        assert!(output.contains("A=1"));
+    }
+
+    #[test]
+    fn test_part2() {
+        let example1 = r#"Register A: 2024
+Register B: 0
+Register C: 0
+
+Program: 0,3,5,4,3,0"#;
+        let expected1 = "117440".to_string();
+
+        assert_eq!(part2(&example1.to_string()), expected1);
+
+        // Not a three bit loop, so the bit-level search has to stop at a sensible size
+        let example2 = r#"Register A: 5
+Register B: 0
+Register C: 0
+
+Program: 0,4,5,4,3,0"#;
+        assert_eq!(part2(&example2.to_string()), "No A below 2^21 prints the program");
+    }
+
+    #[test]
+    fn test_find_register_a() {
+        let program = parse_program("2,4,1,5,7,5,4,3,1,6,0,3,5,5,3,0").unwrap();
+        let (method, candidates) = find_register_a(&program, &parse_program("7,3,5,7,5,7,4,3,0").unwrap(), 0, 0, 64);
+        assert_eq!(method, SearchMethod::Structural);
+        assert!(candidates.contains(&61156655));
+        assert!(candidates.iter().all(|a| execute(*a, 0, 0, &program) == Some(vec![7, 3, 5, 7, 5, 7, 4, 3, 0])));
+
+        // Shifts A by one bit per output, so the 3 bit assumption doesn't hold
+        let program = parse_program("0,1,5,4,3,0").unwrap();
+        let (method, candidates) = find_register_a(&program, &parse_program("4,6,3,5,6,3,5,2,1,0").unwrap(), 0, 0, 16);
+        assert_eq!(method, SearchMethod::BitLevel);
+        assert_eq!(candidates, vec![728, 729]);
+
+        // No loop at all
+        let program = parse_program("2,4,1,3,5,5").unwrap();
+        let (method, candidates) = find_register_a(&program, &[6], 0, 0, 5);
+        assert_eq!(method, SearchMethod::BitLevel);
+        assert_eq!(candidates, vec![5, 13, 21, 29]);
+
+        let program = parse_program("0,3,5,4,3,0").unwrap();
+        let (method, candidates) = find_register_a(&program, &program, 0, 0, 64);
+        assert_eq!(method, SearchMethod::Structural);
+        assert_eq!(candidates, (117440..117448).collect::<Vec<u64>>());
+        assert_eq!(find_register_a(&program, &program, 0, 0, 20).1, candidates);
+        assert!(find_register_a(&program, &program, 0, 0, 16).1.is_empty());
+    }
+
+    #[test]
+    fn test_machine() {
+        let example1 = r#"
+
+Program: 0,1,5,4,3,0
+Register C: 0
+
+Register A: 729
+"#;
+
+        let machine = example1.parse::<Machine>().unwrap();
+        assert_eq!(machine, Machine { a: 729, b: 0, c: 0, program: vec![0, 1, 5, 4, 3, 0] });
+        assert_eq!(machine.to_string(), "Register A: 729\nRegister B: 0\nRegister C: 0\n\nProgram: 0,1,5,4,3,0");
+        assert_eq!(machine.to_string().parse::<Machine>(), Ok(machine));
+
+        assert_eq!("Register A: 1\nProgram: 0,7".parse::<Machine>(), Err("Reserved combo operand 7 at 0".to_string()));
+        assert_eq!("Register A: 1\nProgram: 1,7,8,0".parse::<Machine>(), Err("Value at 2 is not 3 bits".to_string()));
+        assert_eq!("Register A: 1\nProgram: 1,7,3".parse::<Machine>(), Err("Program has an opcode without an operand".to_string()));
+        assert_eq!("Register A: 1\nRegister A: 2\nProgram: 3,0".parse::<Machine>(), Err("Register A given twice".to_string()));
+        assert_eq!("Register B: 1\nProgram: 3,0".parse::<Machine>(), Err("Missing Register A".to_string()));
+        assert_eq!("Register A: 1".parse::<Machine>(), Err("Missing program".to_string()));
+
+        let mut debugger = Debugger::new(729, 0, 0, &[0, 1, 5, 4, 3, 0]);
+        debugger.step();
+        assert_eq!(debugger.snapshot().to_string(), "Register A: 364\nRegister B: 0\nRegister C: 0\n\nProgram: 0,1,5,4,3,0");
+    }
+
+    #[test]
+    fn test_compile() {
+        let instructions = compile(&parse_program("2,4,1,5,7,5,4,3,1,6,0,3,5,5,3,0").unwrap());
+        assert_eq!(instructions[0], Instruction::Bst(Combo::A));
+        assert_eq!(instructions[4], Instruction::Cdv(Combo::B));
+        assert_eq!(instructions[6], Instruction::Bxc);
+        assert_eq!(instructions[14], Instruction::Jnz(0));
+        assert_eq!(compile(&[0, 7, 1, 7])[..], [Instruction::Invalid, Instruction::Cdv(Combo::Literal(1)), Instruction::Bxl(7)]);
+
+        let mut output = Vec::new();
+        assert!(execute_compiled(&instructions, 61156655, 0, 0, &mut output));
+        assert_eq!(output, vec![7, 3, 5, 7, 5, 7, 4, 3, 0]);
+        assert!(!execute_compiled(&compile(&[3, 0]), 1, 0, 0, &mut output));
+        assert!(!execute_compiled(&compile(&[0, 7]), 1, 0, 0, &mut output));
+    }
+
+    #[test]
+    fn test_execute_batch() {
+        // The last one jumps to an odd address
+        for program in ["2,4,1,5,7,5,4,3,1,6,0,3,5,5,3,0", "0,1,5,4,3,0", "6,4,5,5,7,6,5,6,0,3,3,0", "0,1,5,4,3,3,0,5,3,0"] {
+            let program = parse_program(program).unwrap();
+            let expected = (0..2000).filter_map(|a| execute(a, 3, 9, &program).map(|output| (a, output))).collect::<Vec<(u64, Vec<u8>)>>();
+            assert_eq!(execute_batch(&compile(&program), 0..2000, 3, 9, 3, |_, _| true), expected);
+            assert_eq!(execute_batch(&compile(&program), 0..2000, 3, 9, 1, |_, _| true), expected);
+        }
+
+        let program = parse_program("0,3,5,4,3,0").unwrap();
+        let found = execute_batch(&compile(&program), 100_000..200_000, 0, 0, 4, |_, output| output == program);
+        assert_eq!(found.iter().map(|(a, _)| *a).collect::<Vec<u64>>(), (117440..117448).collect::<Vec<u64>>());
+        assert!(execute_batch(&compile(&program), 5..5, 0, 0, 4, |_, _| true).is_empty());
+    }
 }
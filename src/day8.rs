@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 
+#[derive(Clone)]
 struct Location {
     row: i32,
     col: i32
@@ -22,75 +23,221 @@ fn get_frequencies(input: &String) -> HashMap<char, Vec<Location>> {
     frequencies
 }
 
-fn get_antinode(f1: &Location, f2: &Location, max: i32) -> Option<i32> {
-    let antinode_row = f1.row - (f2.row - f1.row);
-    if antinode_row < 0 || antinode_row >= max {
-        return None
+struct Resonance {
+    collinear: bool,
+    min_harmonic: i32,
+    max_harmonic: Option<i32>
+}
+
+impl Resonance {
+    fn paired() -> Resonance {
+        Resonance { collinear: false, min_harmonic: 1, max_harmonic: Some(1) }
     }
 
-    let antinode_col = f1.col - (f2.col - f1.col);
-    if antinode_col < 0 || antinode_col >= max {
-        return None
+    fn collinear() -> Resonance {
+        Resonance { collinear: true, min_harmonic: 0, max_harmonic: None }
     }
 
-    Some(antinode_row * 100 + antinode_col)
+    fn includes(&self, harmonic: i32) -> bool {
+        harmonic >= self.min_harmonic && self.max_harmonic.is_none_or(|max_harmonic| harmonic <= max_harmonic)
+    }
 }
 
-fn get_antinodes(frequencies: HashMap<char, Vec<Location>>, max: i32) -> HashSet<i32> {
-    let mut antinodes: HashSet<i32> = HashSet::new();
-    for locations in frequencies.values() {
+struct AntinodeReport {
+    antinodes: HashMap<char, HashSet<i32>>,
+    overlaps: Vec<(char, char, usize)>,
+    shared: usize
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+fn is_on_map(row: i32, col: i32, max: i32) -> bool {
+    row >= 0 && row < max && col >= 0 && col < max
+}
+
+// Walks the line through both antennas in steps of (row_step, col_step). Positions beyond f1 have harmonic
+// 1, 2, ...; positions from f1 up to f2 (inclusive) have harmonic 0; positions beyond f2 count up again.
+fn add_antinodes_for_pair(f1: &Location, f2: &Location, max: i32, resonance: &Resonance, antinodes: &mut HashSet<i32>) {
+    let row_delta = f2.row - f1.row;
+    let col_delta = f2.col - f1.col;
+    let steps_between = if resonance.collinear { gcd(row_delta, col_delta) } else { 1 };
+    let row_step = row_delta / steps_between;
+    let col_step = col_delta / steps_between;
+
+    if resonance.includes(0) {
+        for step in 0..steps_between + 1 {
+            antinodes.insert((f1.row + row_step * step) * 100 + f1.col + col_step * step);
+        }
+    }
+
+    for (origin, direction) in [(f1, -1), (f2, 1)] {
+        let mut harmonic = resonance.min_harmonic.max(1);
+        loop {
+            if !resonance.includes(harmonic) {
+                break;
+            }
+
+            let antinode_row = origin.row + row_step * harmonic * direction;
+            let antinode_col = origin.col + col_step * harmonic * direction;
+            if !is_on_map(antinode_row, antinode_col, max) {
+                break;
+            }
+
+            antinodes.insert(antinode_row * 100 + antinode_col);
+
+            harmonic += 1;
+        }
+    }
+}
+
+fn get_antinodes_by_frequency(frequencies: &HashMap<char, Vec<Location>>, max: i32, resonance: &Resonance) -> HashMap<char, HashSet<i32>> {
+    let mut antinodes_by_frequency: HashMap<char, HashSet<i32>> = HashMap::new();
+    for (&frequency, locations) in frequencies.iter() {
+        let mut antinodes: HashSet<i32> = HashSet::new();
         for f1 in 0..locations.len() {
             for f2 in f1 + 1..locations.len() {
-                get_antinode(&locations[f1], &locations[f2], max).inspect(|&antinode| { antinodes.insert(antinode); });
-                get_antinode(&locations[f2], &locations[f1], max).inspect(|&antinode| { antinodes.insert(antinode); });
+                add_antinodes_for_pair(&locations[f1], &locations[f2], max, resonance, &mut antinodes);
             }
         }
+        antinodes_by_frequency.insert(frequency, antinodes);
     }
-    antinodes
+    antinodes_by_frequency
 }
 
-fn add_harmonic_antinodes_for_frequency(f1: &Location, f2: &Location, max: i32, antinodes: &mut HashSet<i32>) {
-    let row_delta = f2.row - f1.row;
-    let col_delta = f2.col - f1.col;
-    let mut harmonic = 1;
+fn get_antinodes(frequencies: &HashMap<char, Vec<Location>>, max: i32, resonance: &Resonance) -> HashSet<i32> {
+    get_antinodes_by_frequency(frequencies, max, resonance).into_values().flatten().collect()
+}
 
-    loop {
-        let antinode_row = f1.row - row_delta * harmonic;
-        if antinode_row < 0 || antinode_row >= max {
-            break;
-        }
+fn get_antinode_report(frequencies: &HashMap<char, Vec<Location>>, max: i32, resonance: &Resonance) -> AntinodeReport {
+    let antinodes = get_antinodes_by_frequency(frequencies, max, resonance);
 
-        let antinode_col = f1.col - col_delta * harmonic;
-        if antinode_col < 0 || antinode_col >= max {
-            break;
+    let mut labels: Vec<char> = antinodes.keys().copied().collect();
+    labels.sort();
+    let mut overlaps = Vec::new();
+    for first in 0..labels.len() {
+        for second in first + 1..labels.len() {
+            let overlap = antinodes[&labels[first]].intersection(&antinodes[&labels[second]]).count();
+            if overlap != 0 {
+                overlaps.push((labels[first], labels[second], overlap));
+            }
         }
+    }
 
-        antinodes.insert(antinode_row * 100 + antinode_col);
-
-        harmonic += 1;
+    let mut frequency_counts: HashMap<i32, usize> = HashMap::new();
+    for antinode in antinodes.values().flatten() {
+        *frequency_counts.entry(*antinode).or_insert(0) += 1;
     }
+    let shared = frequency_counts.values().filter(|&&count| count > 1).count();
+
+    AntinodeReport { antinodes, overlaps, shared }
+}
+
+#[derive(Debug, PartialEq)]
+enum Change {
+    Add,
+    Remove
 }
 
-fn get_harmonic_antinodes(frequencies: HashMap<char, Vec<Location>>, max: i32) -> HashSet<i32> {
+enum Goal {
+    Maximise,
+    Minimise
+}
+
+#[derive(Debug, PartialEq)]
+struct Suggestion {
+    change: Change,
+    row: i32,
+    col: i32,
+    antinodes: usize
+}
+
+fn get_pair_antinodes(f1: &Location, f2: &Location, max: i32, resonance: &Resonance) -> HashSet<i32> {
     let mut antinodes: HashSet<i32> = HashSet::new();
+    add_antinodes_for_pair(f1, f2, max, resonance, &mut antinodes);
+    antinodes
+}
+
+fn get_coverage(frequencies: &HashMap<char, Vec<Location>>, max: i32, resonance: &Resonance) -> HashMap<i32, usize> {
+    let mut coverage: HashMap<i32, usize> = HashMap::new();
     for locations in frequencies.values() {
         for f1 in 0..locations.len() {
-            antinodes.insert(locations[f1].row * 100 + locations[f1].col);
             for f2 in f1 + 1..locations.len() {
-                add_harmonic_antinodes_for_frequency(&locations[f1], &locations[f2], max, &mut antinodes);
-                add_harmonic_antinodes_for_frequency(&locations[f2], &locations[f1], max, &mut antinodes);
+                for antinode in get_pair_antinodes(&locations[f1], &locations[f2], max, resonance) {
+                    *coverage.entry(antinode).or_insert(0) += 1;
+                }
             }
         }
     }
-    antinodes
+    coverage
+}
+
+fn is_better(candidate: &Suggestion, best: &Option<Suggestion>, goal: &Goal) -> bool {
+    match best {
+        None => true,
+        Some(best) => match goal {
+            Goal::Maximise => candidate.antinodes > best.antinodes,
+            Goal::Minimise => candidate.antinodes < best.antinodes,
+        }
+    }
+}
+
+// The coverage map counts how many antenna pairs produce each antinode, so a candidate only has to look at the
+// pairs it takes part in: an added antenna gains the positions nobody covers yet, a removed antenna loses the
+// positions it is the only contributor to.
+fn suggest_placement(frequencies: &HashMap<char, Vec<Location>>, frequency: char, max: i32, resonance: &Resonance, goal: &Goal) -> Option<Suggestion> {
+    let coverage = get_coverage(frequencies, max, resonance);
+    let covered = coverage.len();
+    let antennas: &[Location] = frequencies.get(&frequency).map_or(&[], |locations| locations.as_slice());
+    let occupied: HashSet<i32> = frequencies.values().flatten().map(|location| location.row * 100 + location.col).collect();
+    let mut best: Option<Suggestion> = None;
+
+    for row in 0..max {
+        for col in 0..max {
+            if occupied.contains(&(row * 100 + col)) {
+                continue;
+            }
+
+            let candidate = Location { row, col };
+            let mut gained: HashSet<i32> = HashSet::new();
+            for antenna in antennas {
+                gained.extend(get_pair_antinodes(&candidate, antenna, max, resonance).into_iter().filter(|antinode| !coverage.contains_key(antinode)));
+            }
+
+            let suggestion = Suggestion { change: Change::Add, row, col, antinodes: covered + gained.len() };
+            if is_better(&suggestion, &best, goal) {
+                best = Some(suggestion);
+            }
+        }
+    }
+
+    for (index, antenna) in antennas.iter().enumerate() {
+        let mut contributions: HashMap<i32, usize> = HashMap::new();
+        for (other_index, other) in antennas.iter().enumerate() {
+            if other_index != index {
+                for antinode in get_pair_antinodes(antenna, other, max, resonance) {
+                    *contributions.entry(antinode).or_insert(0) += 1;
+                }
+            }
+        }
+        let lost = contributions.iter().filter(|(antinode, &count)| coverage[antinode] == count).count();
+
+        let suggestion = Suggestion { change: Change::Remove, row: antenna.row, col: antenna.col, antinodes: covered - lost };
+        if is_better(&suggestion, &best, goal) {
+            best = Some(suggestion);
+        }
+    }
+
+    best
 }
 
 fn part1(input: &String) -> String {
-    format!("{}", get_antinodes(get_frequencies(input), input.lines().count() as i32).len())
+    format!("{}", get_antinodes(&get_frequencies(input), input.lines().count() as i32, &Resonance::paired()).len())
 }
 
 fn part2(input: &String) -> String {
-    format!("{}", get_harmonic_antinodes(get_frequencies(input), input.lines().count() as i32).len())
+    format!("{}", get_antinodes(&get_frequencies(input), input.lines().count() as i32, &Resonance::collinear()).len())
 }
 
 fn main() {
@@ -99,13 +246,38 @@ fn main() {
     println!("Part1: {}", part1(input1));
 
     println!("Part2: {}", part2(input1));
+
+    if std::env::args().any(|arg| arg == "--report") {
+        let report = get_antinode_report(&get_frequencies(input1), input1.lines().count() as i32, &Resonance::collinear());
+        let mut labels: Vec<&char> = report.antinodes.keys().collect();
+        labels.sort();
+        for label in labels {
+            println!("{}: {} antinodes", label, report.antinodes[label].len());
+        }
+        for (first, second, overlap) in &report.overlaps {
+            println!("{} & {}: {} shared", first, second, overlap);
+        }
+        println!("Shared by several frequencies: {}", report.shared);
+    }
+
+    if std::env::args().any(|arg| arg == "--suggest") {
+        let frequencies = get_frequencies(input1);
+        let mut labels: Vec<&char> = frequencies.keys().collect();
+        labels.sort();
+        for &label in labels {
+            let max = input1.lines().count() as i32;
+            let most = suggest_placement(&frequencies, label, max, &Resonance::paired(), &Goal::Maximise).unwrap();
+            let least = suggest_placement(&frequencies, label, max, &Resonance::paired(), &Goal::Minimise).unwrap();
+            println!("{}: {:?} ({}, {}) for {} antinodes, {:?} ({}, {}) for {} antinodes", label,
+                     most.change, most.row, most.col, most.antinodes, least.change, least.row, least.col, least.antinodes);
+        }
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::part1;
-    use crate::part2;
+    use crate::{get_antinode_report, get_antinodes, get_frequencies, part1, part2, suggest_placement, Change, Goal, Location, Resonance, Suggestion};
 
     #[test]
     fn test_part1() {
@@ -144,4 +316,99 @@ mod tests {
 
         assert_eq!(part2(&example1.to_string()), expected1);
     }
+
+    #[test]
+    fn test_collinear_steps_between_antennas() {
+        let example1 = r#".....
+.....
+..a..
+.....
+a....
+"#.to_string();
+
+        let frequencies = get_frequencies(&example1);
+        let collinear = get_antinodes(&frequencies, 5, &Resonance::collinear());
+        let mut expected: Vec<i32> = vec![400, 301, 202, 103, 4];
+        let mut actual: Vec<i32> = collinear.into_iter().collect();
+        actual.sort();
+        expected.sort();
+        assert_eq!(actual, expected);
+
+        let limited = Resonance { collinear: true, min_harmonic: 1, max_harmonic: Some(1) };
+        let mut actual: Vec<i32> = get_antinodes(&frequencies, 5, &limited).into_iter().collect();
+        actual.sort();
+        assert_eq!(actual, vec![103]);
+    }
+
+    #[test]
+    fn test_antinode_report() {
+        let example1 = r#"............
+........0...
+.....0......
+.......0....
+....0.......
+......A.....
+............
+............
+........A...
+.........A..
+............
+............"#.to_string();
+
+        let report = get_antinode_report(&get_frequencies(&example1), 12, &Resonance::paired());
+        assert_eq!(report.antinodes[&'0'].len(), 10);
+        assert_eq!(report.antinodes[&'A'].len(), 5);
+        assert_eq!(report.overlaps, vec![('0', 'A', 1)]);
+        assert_eq!(report.shared, 1);
+    }
+
+    #[test]
+    fn test_suggest_placement() {
+        let example1 = r#"..........
+..........
+..........
+....a.....
+..........
+.....a....
+..........
+..........
+..........
+.........."#.to_string();
+
+        let frequencies = get_frequencies(&example1);
+        let most = suggest_placement(&frequencies, 'a', 10, &Resonance::paired(), &Goal::Maximise);
+        assert_eq!(most, Some(Suggestion { change: Change::Add, row: 3, col: 3, antinodes: 6 }));
+        let least = suggest_placement(&frequencies, 'a', 10, &Resonance::paired(), &Goal::Minimise);
+        assert_eq!(least, Some(Suggestion { change: Change::Remove, row: 3, col: 4, antinodes: 0 }));
+    }
+
+    #[test]
+    fn test_suggest_placement_matches_recount() {
+        let example1 = r#"............
+........0...
+.....0......
+.......0....
+....0.......
+......A.....
+............
+............
+........A...
+.........A..
+............
+............"#.to_string();
+
+        let frequencies = get_frequencies(&example1);
+        for goal in [Goal::Maximise, Goal::Minimise] {
+            for resonance in [Resonance::paired(), Resonance::collinear()] {
+                let suggestion = suggest_placement(&frequencies, 'A', 12, &resonance, &goal).unwrap();
+                let mut changed = frequencies.clone();
+                let antennas = changed.get_mut(&'A').unwrap();
+                match suggestion.change {
+                    Change::Add => antennas.push(Location { row: suggestion.row, col: suggestion.col }),
+                    Change::Remove => antennas.retain(|antenna| antenna.row != suggestion.row || antenna.col != suggestion.col),
+                }
+                assert_eq!(get_antinodes(&changed, 12, &resonance).len(), suggestion.antinodes);
+            }
+        }
+    }
 }
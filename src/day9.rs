@@ -1,60 +1,218 @@
 use std::cmp::max;
-use std::collections::{HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt;
+use rand::Rng;
 
 fn char_to_int(c: char) -> i64 {
     c as i64 - 0x30
 }
 
-fn part1(input: &String) -> String {
-    let mut forward_iter = input.chars();
-    let mut backward_iter = input.chars().rev();
-    let mut forward_pos = 0;
-    let mut backward_pos: i64 = input.len() as i64 - 1;
-    let mut checksum = 0;
-    let mut disk_pos = 0;
-    let mut size_of_last_file = 0;
-    
-    loop {
-        let file_size = forward_iter.next().map(char_to_int).unwrap();
-        checksum += (disk_pos..disk_pos + file_size).sum::<i64>() * (forward_pos / 2);
-        disk_pos += file_size;
-        forward_pos += 1;
-
-        if forward_pos >= backward_pos {
-            if size_of_last_file != 0 {
-                checksum += (disk_pos..disk_pos + size_of_last_file).sum::<i64>() * ((forward_pos + 1) / 2);
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Extent {
+    position: i64,
+    size: i64
+}
+
+struct File {
+    id: i64,
+    extents: Vec<Extent>
+}
+
+impl File {
+    fn size(&self) -> i64 {
+        self.extents.iter().map(|extent| extent.size).sum()
+    }
+
+    fn position(&self) -> Option<i64> {
+        self.extents.iter().filter(|extent| extent.size != 0).map(|extent| extent.position).min()
+    }
+}
+
+struct Disk {
+    files: Vec<File>,
+    free: Vec<Extent>
+}
+
+enum Compaction {
+    Block,
+    FirstFit,
+    BestFit,
+    Defragment
+}
+
+impl Disk {
+    fn parse(input: &str) -> Disk {
+        let mut disk = Disk { files: Vec::new(), free: Vec::new() };
+        let mut position = 0;
+        for (index, block) in input.trim().chars().enumerate() {
+            let size = char_to_int(block);
+            if index % 2 == 0 {
+                disk.files.push(File { id: index as i64 / 2, extents: vec![Extent { position, size }] });
+            } else if size != 0 {
+                disk.free.push(Extent { position, size });
+            }
+            position += size;
+        }
+        disk
+    }
+
+    fn size(&self) -> i64 {
+        self.files.iter().flat_map(|file| file.extents.iter()).chain(self.free.iter())
+            .map(|extent| extent.position + extent.size)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn blocks(&self) -> Vec<Option<i64>> {
+        let mut blocks = vec![None; self.size() as usize];
+        for file in &self.files {
+            for extent in &file.extents {
+                for position in extent.position..extent.position + extent.size {
+                    blocks[position as usize] = Some(file.id);
+                }
+            }
+        }
+        blocks
+    }
+
+    fn set_blocks(&mut self, blocks: &[Option<i64>]) {
+        for file in &mut self.files {
+            file.extents.clear();
+        }
+        self.free.clear();
+
+        let mut position = 0;
+        for run in blocks.chunk_by(|first, second| first == second) {
+            let extent = Extent { position, size: run.len() as i64 };
+            match run[0] {
+                None => self.free.push(extent),
+                Some(id) => self.files[id as usize].extents.push(extent),
             }
-            break;
+            position += extent.size;
+        }
+    }
+
+    fn release(&mut self, extent: Extent) {
+        if extent.size == 0 {
+            return
+        }
+        let index = self.free.partition_point(|free| free.position < extent.position);
+        self.free.insert(index, extent);
+        if index + 1 < self.free.len() && extent.position + extent.size == self.free[index + 1].position {
+            self.free[index].size += self.free.remove(index + 1).size;
+        }
+        if index > 0 && self.free[index - 1].position + self.free[index - 1].size == extent.position {
+            self.free[index - 1].size += self.free.remove(index).size;
         }
+    }
 
-        let mut space_size = forward_iter.next().map(char_to_int).unwrap();
-        forward_pos += 1;
+    fn compact(&mut self, compaction: &Compaction) {
+        match compaction {
+            Compaction::Block => self.compact_blocks(),
+            Compaction::FirstFit => self.compact_files(false),
+            Compaction::BestFit => self.compact_files(true),
+            Compaction::Defragment => self.defragment(),
+        }
+    }
 
+    fn compact_blocks(&mut self) {
+        let mut blocks = self.blocks();
+        if blocks.is_empty() {
+            return
+        }
+
+        let mut left = 0;
+        let mut right = blocks.len() - 1;
         loop {
-            if space_size == 0 {
+            while left < right && blocks[left].is_some() {
+                left += 1;
+            }
+            while left < right && blocks[right].is_none() {
+                right -= 1;
+            }
+            if left >= right {
                 break;
             }
-            if size_of_last_file == 0 {
-                size_of_last_file = backward_iter.next().map(char_to_int).unwrap();
-                backward_iter.next().unwrap();
-                backward_pos -= 2;
+            blocks.swap(left, right);
+        }
+
+        self.set_blocks(&blocks);
+    }
+
+    fn compact_files(&mut self, best_fit: bool) {
+        for index in (0..self.files.len()).rev() {
+            let size = self.files[index].size();
+            let file_position = match self.files[index].position() {
+                None => continue,
+                Some(position) => position
+            };
+
+            let candidates = self.free.iter().enumerate()
+                .take_while(|(_, free)| free.position < file_position)
+                .filter(|(_, free)| free.size >= size);
+            let chosen = if best_fit {
+                candidates.min_by_key(|(_, free)| (free.size, free.position))
+            } else {
+                candidates.min_by_key(|(_, free)| free.position)
+            };
+
+            if let Some((free_index, _)) = chosen {
+                let target = self.free[free_index].position;
+                self.free[free_index].position += size;
+                self.free[free_index].size -= size;
+                if self.free[free_index].size == 0 {
+                    self.free.remove(free_index);
+                }
+
+                for extent in std::mem::replace(&mut self.files[index].extents, vec![Extent { position: target, size }]) {
+                    self.release(extent);
+                }
             }
-            let amount_to_copy = space_size.min(size_of_last_file);
-            space_size -= amount_to_copy;
-            size_of_last_file -= amount_to_copy;
-            checksum += (disk_pos..disk_pos + amount_to_copy).sum::<i64>() * ((backward_pos + 2) / 2);
-            disk_pos += amount_to_copy;
+        }
+    }
+
+    fn defragment(&mut self) {
+        let disk_size = self.size();
+        let mut order: Vec<usize> = (0..self.files.len()).filter(|&index| self.files[index].position().is_some()).collect();
+        order.sort_by_key(|&index| self.files[index].position());
+
+        let mut position = 0;
+        for index in order {
+            let size = self.files[index].size();
+            self.files[index].extents = vec![Extent { position, size }];
+            position += size;
         }
 
-        if forward_pos >= backward_pos {
-            if size_of_last_file != 0 {
-                checksum += (disk_pos..disk_pos + size_of_last_file).sum::<i64>() * ((backward_pos + 2) / 2);
+        self.free.clear();
+        self.release(Extent { position, size: disk_size - position });
+    }
+
+    fn checksum(&self) -> i64 {
+        let mut checksum = 0;
+        for file in &self.files {
+            for extent in &file.extents {
+                checksum += (extent.position..extent.position + extent.size).sum::<i64>() * file.id;
             }
-            break;
         }
+        checksum
     }
+}
 
-    format!("{}", checksum)
+impl fmt::Display for Disk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let layout: String = self.blocks().iter().map(|block| match block {
+            None => '.',
+            Some(id) => char::from_digit((id % 10) as u32, 10).unwrap(),
+        }).collect();
+        write!(f, "{}", layout)
+    }
+}
+
+fn part1(input: &String) -> String {
+    let mut disk = Disk::parse(input);
+    disk.compact(&Compaction::Block);
+    format!("{}", disk.checksum())
 }
 
 fn part2(input: &String) -> String {
@@ -141,19 +299,92 @@ fn part2(input: &String) -> String {
     format!("{}", checksum)
 }
 
+// Free spans are kept in one min-heap of positions per span size, so each file only has to compare the leftmost
+// span of every size it fits in. Spans that are right of the file can be left in the heaps: every file after it
+// starts further left.
+fn compact_whole_files(input: &str) -> i64 {
+    let mut files: Vec<(i64, i64)> = Vec::new();
+    let mut free_spans: Vec<BinaryHeap<Reverse<i64>>> = vec![BinaryHeap::new(); 10];
+    let mut position = 0;
+    for (index, block) in input.trim().chars().enumerate() {
+        let size = char_to_int(block);
+        if index % 2 == 0 {
+            files.push((position, size));
+        } else if size != 0 {
+            free_spans[size as usize].push(Reverse(position));
+        }
+        position += size;
+    }
+
+    let mut checksum = 0;
+    for (id, &(file_position, size)) in files.iter().enumerate().rev() {
+        let mut chosen: Option<(usize, i64)> = None;
+        for (span_size, spans) in free_spans.iter().enumerate().skip(size as usize) {
+            if let Some(&Reverse(span_position)) = spans.peek() {
+                if span_position < file_position && chosen.is_none_or(|(_, best_position)| span_position < best_position) {
+                    chosen = Some((span_size, span_position));
+                }
+            }
+        }
+
+        let target = match chosen {
+            None => file_position,
+            Some((span_size, span_position)) => {
+                free_spans[span_size].pop();
+                if span_size as i64 > size {
+                    free_spans[span_size - size as usize].push(Reverse(span_position + size));
+                }
+                span_position
+            }
+        };
+        checksum += size * (2 * target + size - 1) / 2 * id as i64;
+    }
+
+    checksum
+}
+
+fn verify_compactor(rounds: usize, file_count: usize) -> bool {
+    let mut rng = rand::rng();
+    for _ in 0..rounds {
+        let disk_map: String = (0..(file_count * 2).saturating_sub(1))
+            .map(|index| if index % 2 == 0 { rng.random_range(1..10) } else { rng.random_range(0..10) })
+            .map(|size| char::from_digit(size, 10).unwrap())
+            .collect();
+
+        let expected = part2(&disk_map);
+        let actual = format!("{}", compact_whole_files(&disk_map));
+        if actual != expected {
+            println!("{}: Expected {} was {}", disk_map, expected, actual);
+            return false
+        }
+    }
+    true
+}
+
 fn main() {
     env_logger::init();
     let input1 = &std::fs::read_to_string("day9.txt").expect("Unable to read input file");
     println!("Part1: {}", part1(input1));
 
     println!("Part2: {}", part2(input1));
+    println!("Part2 (heaps): {}", compact_whole_files(input1));
+
+    if std::env::args().any(|arg| arg == "--verify") {
+        println!("Compactor matches part2: {}", verify_compactor(1000, 200));
+    }
+
+    for (name, compaction) in [("Block", Compaction::Block), ("First fit", Compaction::FirstFit), ("Best fit", Compaction::BestFit), ("Defragment", Compaction::Defragment)] {
+        let mut disk = Disk::parse(input1);
+        disk.compact(&compaction);
+        let layout = disk.to_string();
+        println!("{}: {} {}...", name, disk.checksum(), &layout[..layout.len().min(60)]);
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::part1;
-    use crate::part2;
+    use crate::{compact_whole_files, part1, part2, verify_compactor, Compaction, Disk};
 
     #[test]
     fn test_part1() {
@@ -170,4 +401,62 @@ mod tests {
 
         assert_eq!(part2(&example1.to_string()), expected1);
     }
+
+    #[test]
+    fn test_compaction_layouts() {
+        let example1 = r#"2333133121414131402"#;
+
+        let mut disk = Disk::parse(example1);
+        assert_eq!(disk.to_string(), "00...111...2...333.44.5555.6666.777.888899");
+
+        disk.compact(&Compaction::Block);
+        assert_eq!(disk.to_string(), "0099811188827773336446555566..............");
+        assert_eq!(disk.checksum(), 1928);
+
+        let mut disk = Disk::parse(example1);
+        disk.compact(&Compaction::FirstFit);
+        assert_eq!(disk.to_string(), "00992111777.44.333....5555.6666.....8888..");
+        assert_eq!(disk.checksum(), 2858);
+
+        let mut disk = Disk::parse(example1);
+        disk.compact(&Compaction::Defragment);
+        assert_eq!(disk.to_string(), "0011123334455556666777888899..............");
+        assert_eq!(disk.free.len(), 1);
+    }
+
+    #[test]
+    fn test_best_fit() {
+        let example1 = r#"1312321"#;
+
+        let mut disk = Disk::parse(example1);
+        assert_eq!(disk.to_string(), "0...1..222..3");
+        disk.compact(&Compaction::BestFit);
+        assert_eq!(disk.to_string(), "022213.......");
+
+        let mut disk = Disk::parse(example1);
+        disk.compact(&Compaction::FirstFit);
+        assert_eq!(disk.to_string(), "031....222...");
+    }
+
+    #[test]
+    fn test_first_fit_matches_part2() {
+        let input = std::fs::read_to_string("day9.txt").expect("Unable to read input file");
+
+        let mut disk = Disk::parse(&input);
+        disk.compact(&Compaction::FirstFit);
+        assert_eq!(format!("{}", disk.checksum()), part2(&input));
+    }
+
+    #[test]
+    fn test_compact_whole_files() {
+        assert_eq!(compact_whole_files("2333133121414131402"), 2858);
+        assert_eq!(compact_whole_files("1312321"), 53);
+    }
+
+    #[test]
+    fn test_compactor_against_part2() {
+        assert!(verify_compactor(200, 50));
+        assert!(verify_compactor(5, 0));
+        assert!(verify_compactor(5, 1));
+    }
 }